@@ -0,0 +1,12 @@
+-- Add down migration script here
+
+DROP INDEX idx_dhash_block_0;
+DROP INDEX idx_dhash_block_1;
+DROP INDEX idx_dhash_block_2;
+DROP INDEX idx_dhash_block_3;
+
+ALTER TABLE artist_artworks
+    DROP COLUMN dhash_block_0,
+    DROP COLUMN dhash_block_1,
+    DROP COLUMN dhash_block_2,
+    DROP COLUMN dhash_block_3;
@@ -0,0 +1,15 @@
+-- Add up migration script here
+
+-- split every dhash into four 16-bit blocks for multi-index hashing. any two hashes within a
+-- Hamming distance of `r` must have at least one block within a distance of `r / 4`, so a radius
+-- query only has to look up a handful of block values per index.
+ALTER TABLE artist_artworks
+    ADD COLUMN dhash_block_0 INTEGER GENERATED ALWAYS AS ((get_byte(dhash, 0) << 8) | get_byte(dhash, 1)) STORED,
+    ADD COLUMN dhash_block_1 INTEGER GENERATED ALWAYS AS ((get_byte(dhash, 2) << 8) | get_byte(dhash, 3)) STORED,
+    ADD COLUMN dhash_block_2 INTEGER GENERATED ALWAYS AS ((get_byte(dhash, 4) << 8) | get_byte(dhash, 5)) STORED,
+    ADD COLUMN dhash_block_3 INTEGER GENERATED ALWAYS AS ((get_byte(dhash, 6) << 8) | get_byte(dhash, 7)) STORED;
+
+CREATE INDEX idx_dhash_block_0 ON artist_artworks(dhash_block_0);
+CREATE INDEX idx_dhash_block_1 ON artist_artworks(dhash_block_1);
+CREATE INDEX idx_dhash_block_2 ON artist_artworks(dhash_block_2);
+CREATE INDEX idx_dhash_block_3 ON artist_artworks(dhash_block_3);
@@ -0,0 +1,14 @@
+-- Add down migration script here
+
+DROP INDEX idx_phash;
+DROP INDEX idx_phash_block_0;
+DROP INDEX idx_phash_block_1;
+DROP INDEX idx_phash_block_2;
+DROP INDEX idx_phash_block_3;
+
+ALTER TABLE artist_artworks
+    DROP COLUMN phash,
+    DROP COLUMN phash_block_0,
+    DROP COLUMN phash_block_1,
+    DROP COLUMN phash_block_2,
+    DROP COLUMN phash_block_3;
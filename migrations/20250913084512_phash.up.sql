@@ -0,0 +1,16 @@
+-- Add up migration script here
+
+-- existing artworks are left without a phash, since scraping never revisits them, so they can't be
+-- matched by it until `jane-doe rehash` backfills it.
+ALTER TABLE artist_artworks
+    ADD COLUMN phash BYTEA,
+    ADD COLUMN phash_block_0 INTEGER GENERATED ALWAYS AS ((get_byte(phash, 0) << 8) | get_byte(phash, 1)) STORED,
+    ADD COLUMN phash_block_1 INTEGER GENERATED ALWAYS AS ((get_byte(phash, 2) << 8) | get_byte(phash, 3)) STORED,
+    ADD COLUMN phash_block_2 INTEGER GENERATED ALWAYS AS ((get_byte(phash, 4) << 8) | get_byte(phash, 5)) STORED,
+    ADD COLUMN phash_block_3 INTEGER GENERATED ALWAYS AS ((get_byte(phash, 6) << 8) | get_byte(phash, 7)) STORED;
+
+CREATE INDEX idx_phash ON artist_artworks(phash);
+CREATE INDEX idx_phash_block_0 ON artist_artworks(phash_block_0);
+CREATE INDEX idx_phash_block_1 ON artist_artworks(phash_block_1);
+CREATE INDEX idx_phash_block_2 ON artist_artworks(phash_block_2);
+CREATE INDEX idx_phash_block_3 ON artist_artworks(phash_block_3);
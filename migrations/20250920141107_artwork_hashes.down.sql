@@ -0,0 +1,36 @@
+-- Add down migration script here
+
+ALTER TABLE artist_artworks
+    ADD COLUMN dhash BYTEA,
+    ADD COLUMN phash BYTEA;
+
+UPDATE artist_artworks aw
+SET
+    dhash = (SELECT hash FROM artwork_hashes WHERE artwork_id = aw.id AND algorithm = 'dhash'),
+    phash = (SELECT hash FROM artwork_hashes WHERE artwork_id = aw.id AND algorithm = 'phash');
+
+DELETE FROM artist_artworks WHERE dhash IS NULL;
+
+ALTER TABLE artist_artworks
+    ALTER COLUMN dhash SET NOT NULL,
+    ADD COLUMN dhash_block_0 INTEGER GENERATED ALWAYS AS ((get_byte(dhash, 0) << 8) | get_byte(dhash, 1)) STORED,
+    ADD COLUMN dhash_block_1 INTEGER GENERATED ALWAYS AS ((get_byte(dhash, 2) << 8) | get_byte(dhash, 3)) STORED,
+    ADD COLUMN dhash_block_2 INTEGER GENERATED ALWAYS AS ((get_byte(dhash, 4) << 8) | get_byte(dhash, 5)) STORED,
+    ADD COLUMN dhash_block_3 INTEGER GENERATED ALWAYS AS ((get_byte(dhash, 6) << 8) | get_byte(dhash, 7)) STORED,
+    ADD COLUMN phash_block_0 INTEGER GENERATED ALWAYS AS ((get_byte(phash, 0) << 8) | get_byte(phash, 1)) STORED,
+    ADD COLUMN phash_block_1 INTEGER GENERATED ALWAYS AS ((get_byte(phash, 2) << 8) | get_byte(phash, 3)) STORED,
+    ADD COLUMN phash_block_2 INTEGER GENERATED ALWAYS AS ((get_byte(phash, 4) << 8) | get_byte(phash, 5)) STORED,
+    ADD COLUMN phash_block_3 INTEGER GENERATED ALWAYS AS ((get_byte(phash, 6) << 8) | get_byte(phash, 7)) STORED;
+
+CREATE INDEX idx_dhash ON artist_artworks(dhash);
+CREATE INDEX idx_dhash_block_0 ON artist_artworks(dhash_block_0);
+CREATE INDEX idx_dhash_block_1 ON artist_artworks(dhash_block_1);
+CREATE INDEX idx_dhash_block_2 ON artist_artworks(dhash_block_2);
+CREATE INDEX idx_dhash_block_3 ON artist_artworks(dhash_block_3);
+CREATE INDEX idx_phash ON artist_artworks(phash);
+CREATE INDEX idx_phash_block_0 ON artist_artworks(phash_block_0);
+CREATE INDEX idx_phash_block_1 ON artist_artworks(phash_block_1);
+CREATE INDEX idx_phash_block_2 ON artist_artworks(phash_block_2);
+CREATE INDEX idx_phash_block_3 ON artist_artworks(phash_block_3);
+
+DROP TABLE artwork_hashes;
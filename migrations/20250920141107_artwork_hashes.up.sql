@@ -0,0 +1,49 @@
+-- Add up migration script here
+
+-- every hashing algorithm gets its own row per artwork, so new algorithms don't need a new column.
+-- hashes are split into four 16-bit blocks for multi-index hashing, the same as before.
+CREATE TABLE artwork_hashes (
+    artwork_id BIGINT NOT NULL REFERENCES artist_artworks(id) ON DELETE CASCADE,
+    algorithm TEXT NOT NULL,
+    hash BYTEA NOT NULL,
+    block_0 INTEGER GENERATED ALWAYS AS (CASE WHEN length(hash) = 8 THEN (get_byte(hash, 0) << 8) | get_byte(hash, 1) END) STORED,
+    block_1 INTEGER GENERATED ALWAYS AS (CASE WHEN length(hash) = 8 THEN (get_byte(hash, 2) << 8) | get_byte(hash, 3) END) STORED,
+    block_2 INTEGER GENERATED ALWAYS AS (CASE WHEN length(hash) = 8 THEN (get_byte(hash, 4) << 8) | get_byte(hash, 5) END) STORED,
+    block_3 INTEGER GENERATED ALWAYS AS (CASE WHEN length(hash) = 8 THEN (get_byte(hash, 6) << 8) | get_byte(hash, 7) END) STORED,
+
+    PRIMARY KEY (artwork_id, algorithm)
+);
+
+INSERT INTO artwork_hashes (artwork_id, algorithm, hash)
+SELECT id, 'dhash', dhash FROM artist_artworks
+UNION ALL
+SELECT id, 'phash', phash FROM artist_artworks WHERE phash IS NOT NULL;
+
+CREATE INDEX idx_artwork_hashes_hash ON artwork_hashes(algorithm, hash);
+CREATE INDEX idx_artwork_hashes_block_0 ON artwork_hashes(algorithm, block_0);
+CREATE INDEX idx_artwork_hashes_block_1 ON artwork_hashes(algorithm, block_1);
+CREATE INDEX idx_artwork_hashes_block_2 ON artwork_hashes(algorithm, block_2);
+CREATE INDEX idx_artwork_hashes_block_3 ON artwork_hashes(algorithm, block_3);
+
+DROP INDEX idx_dhash;
+DROP INDEX idx_dhash_block_0;
+DROP INDEX idx_dhash_block_1;
+DROP INDEX idx_dhash_block_2;
+DROP INDEX idx_dhash_block_3;
+DROP INDEX idx_phash;
+DROP INDEX idx_phash_block_0;
+DROP INDEX idx_phash_block_1;
+DROP INDEX idx_phash_block_2;
+DROP INDEX idx_phash_block_3;
+
+ALTER TABLE artist_artworks
+    DROP COLUMN dhash_block_0,
+    DROP COLUMN dhash_block_1,
+    DROP COLUMN dhash_block_2,
+    DROP COLUMN dhash_block_3,
+    DROP COLUMN phash_block_0,
+    DROP COLUMN phash_block_1,
+    DROP COLUMN phash_block_2,
+    DROP COLUMN phash_block_3,
+    DROP COLUMN dhash,
+    DROP COLUMN phash;
@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+DROP INDEX idx_artwork_tiles_block_0;
+DROP INDEX idx_artwork_tiles_block_1;
+DROP INDEX idx_artwork_tiles_block_2;
+DROP INDEX idx_artwork_tiles_block_3;
+
+DROP TABLE artwork_tiles;
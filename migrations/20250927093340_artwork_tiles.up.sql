@@ -0,0 +1,19 @@
+-- Add up migration script here
+
+-- dhashes of every tile of an artwork, for matching cropped or partially edited images.
+CREATE TABLE artwork_tiles (
+    artwork_id BIGINT NOT NULL REFERENCES artist_artworks(id) ON DELETE CASCADE,
+    tile_index SMALLINT NOT NULL,
+    hash BYTEA NOT NULL,
+    block_0 INTEGER GENERATED ALWAYS AS ((get_byte(hash, 0) << 8) | get_byte(hash, 1)) STORED,
+    block_1 INTEGER GENERATED ALWAYS AS ((get_byte(hash, 2) << 8) | get_byte(hash, 3)) STORED,
+    block_2 INTEGER GENERATED ALWAYS AS ((get_byte(hash, 4) << 8) | get_byte(hash, 5)) STORED,
+    block_3 INTEGER GENERATED ALWAYS AS ((get_byte(hash, 6) << 8) | get_byte(hash, 7)) STORED,
+
+    PRIMARY KEY (artwork_id, tile_index)
+);
+
+CREATE INDEX idx_artwork_tiles_block_0 ON artwork_tiles(block_0);
+CREATE INDEX idx_artwork_tiles_block_1 ON artwork_tiles(block_1);
+CREATE INDEX idx_artwork_tiles_block_2 ON artwork_tiles(block_2);
+CREATE INDEX idx_artwork_tiles_block_3 ON artwork_tiles(block_3);
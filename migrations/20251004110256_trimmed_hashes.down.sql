@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE artwork_hashes DROP COLUMN trimmed;
+ALTER TABLE artwork_tiles DROP COLUMN trimmed;
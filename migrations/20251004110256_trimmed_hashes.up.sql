@@ -0,0 +1,6 @@
+-- Add up migration script here
+
+-- whether borders were trimmed off the artwork before hashing, so hashes computed with and
+-- without trimming are never compared against each other.
+ALTER TABLE artwork_hashes ADD COLUMN trimmed BOOLEAN NOT NULL DEFAULT FALSE;
+ALTER TABLE artwork_tiles ADD COLUMN trimmed BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,8 @@
+-- Add down migration script here
+
+DELETE FROM artwork_hashes WHERE frame <> 0;
+
+ALTER TABLE artwork_hashes DROP CONSTRAINT artwork_hashes_pkey;
+ALTER TABLE artwork_hashes ADD PRIMARY KEY (artwork_id, algorithm);
+
+ALTER TABLE artwork_hashes DROP COLUMN frame;
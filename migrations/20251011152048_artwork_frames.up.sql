@@ -0,0 +1,7 @@
+-- Add up migration script here
+
+-- animated artworks get a set of hashes for every sampled frame.
+ALTER TABLE artwork_hashes ADD COLUMN frame SMALLINT NOT NULL DEFAULT 0;
+
+ALTER TABLE artwork_hashes DROP CONSTRAINT artwork_hashes_pkey;
+ALTER TABLE artwork_hashes ADD PRIMARY KEY (artwork_id, algorithm, frame);
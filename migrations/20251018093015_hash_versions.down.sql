@@ -0,0 +1,6 @@
+-- Add down migration script here
+
+DROP INDEX artwork_hashes_outdated_idx;
+
+ALTER TABLE artwork_tiles DROP COLUMN version;
+ALTER TABLE artwork_hashes DROP COLUMN version;
@@ -0,0 +1,8 @@
+-- Add up migration script here
+
+-- every stored hash records the version of the algorithm that computed it, so hashes computed by
+-- an outdated version are never compared with fresh ones.
+ALTER TABLE artwork_hashes ADD COLUMN version SMALLINT NOT NULL DEFAULT 1;
+ALTER TABLE artwork_tiles ADD COLUMN version SMALLINT NOT NULL DEFAULT 1;
+
+CREATE INDEX artwork_hashes_outdated_idx ON artwork_hashes (artwork_id, algorithm, version);
@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+DROP TABLE guild_settings;
+DROP TYPE RESPONSE_MODE;
@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+CREATE TYPE RESPONSE_MODE AS ENUM ('reply_ping', 'reply', 'silent');
+
+CREATE TABLE guild_settings (
+    guild_id BIGINT PRIMARY KEY,
+    enabled BOOLEAN NOT NULL DEFAULT TRUE,
+    match_radius SMALLINT NOT NULL DEFAULT 14,
+    response_mode RESPONSE_MODE NOT NULL DEFAULT 'reply_ping',
+    excluded_channels BIGINT[] NOT NULL DEFAULT '{}',
+    excluded_roles BIGINT[] NOT NULL DEFAULT '{}',
+    log_channel BIGINT,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,14 @@
+-- Add down migration script here
+
+CREATE TYPE RESPONSE_MODE AS ENUM ('reply_ping', 'reply', 'silent');
+
+ALTER TABLE guild_settings ADD COLUMN response_mode RESPONSE_MODE NOT NULL DEFAULT 'reply_ping';
+
+UPDATE guild_settings
+SET response_mode = CASE response_action
+    WHEN 'reply' THEN 'reply'::RESPONSE_MODE
+    WHEN 'log_only' THEN 'silent'::RESPONSE_MODE
+    ELSE 'reply_ping'::RESPONSE_MODE
+END;
+
+ALTER TABLE guild_settings DROP COLUMN response_action;
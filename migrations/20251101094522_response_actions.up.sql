@@ -0,0 +1,12 @@
+-- Add up migration script here
+
+ALTER TABLE guild_settings ADD COLUMN response_action TEXT NOT NULL DEFAULT 'reply_ping';
+
+UPDATE guild_settings
+SET response_action = CASE response_mode
+    WHEN 'silent' THEN 'log_only'
+    ELSE response_mode::TEXT
+END;
+
+ALTER TABLE guild_settings DROP COLUMN response_mode;
+DROP TYPE RESPONSE_MODE;
@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE match_allowlist;
+DROP TABLE detection_feedback;
+DROP TYPE FEEDBACK_VERDICT;
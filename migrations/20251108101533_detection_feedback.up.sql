@@ -0,0 +1,33 @@
+-- Add up migration script here
+
+CREATE TYPE FEEDBACK_VERDICT AS ENUM ('confirmed', 'false_positive');
+
+-- verdicts of moderators on the detections of the bot.
+CREATE TABLE detection_feedback (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT,
+    message_id BIGINT NOT NULL,
+    artwork_id BIGINT NOT NULL REFERENCES artist_artworks(id) ON DELETE CASCADE,
+    image_hash BYTEA NOT NULL,
+    verdict FEEDBACK_VERDICT NOT NULL,
+    moderator_id BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    UNIQUE(message_id, artwork_id, moderator_id)
+);
+
+-- hashes of images reported as false positives, which never match again in their guild, or
+-- anywhere if the guild is null.
+CREATE TABLE match_allowlist (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT,
+    algorithm TEXT NOT NULL,
+    hash BYTEA NOT NULL,
+    artwork_id BIGINT REFERENCES artist_artworks(id) ON DELETE SET NULL,
+    added_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    UNIQUE NULLS NOT DISTINCT (guild_id, algorithm, hash)
+);
+
+CREATE INDEX idx_match_allowlist_guild ON match_allowlist(guild_id);
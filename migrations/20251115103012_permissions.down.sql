@@ -0,0 +1,4 @@
+-- Add down migration script here
+
+ALTER TABLE guild_settings DROP COLUMN manager_roles;
+DROP TABLE curators;
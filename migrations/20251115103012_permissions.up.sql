@@ -0,0 +1,10 @@
+-- Add up migration script here
+
+-- users trusted to manage the known AI artists of every guild.
+CREATE TABLE curators (
+    user_id BIGINT PRIMARY KEY,
+    added_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+ALTER TABLE guild_settings ADD COLUMN manager_roles BIGINT[] NOT NULL DEFAULT '{}';
@@ -0,0 +1,21 @@
+-- Add up migration script here
+
+-- every known AI artwork matched in a message, along with the action taken on the message.
+CREATE TABLE detections (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT,
+    channel_id BIGINT NOT NULL,
+    message_id BIGINT NOT NULL,
+    author_id BIGINT NOT NULL,
+    image_hash BYTEA NOT NULL,
+    artwork_id BIGINT REFERENCES artist_artworks(id) ON DELETE SET NULL,
+    distance SMALLINT NOT NULL,
+    confidence REAL NOT NULL,
+    action TEXT NOT NULL,
+    action_succeeded BOOLEAN NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_detections_guild_time ON detections(guild_id, detected_at DESC);
+CREATE INDEX idx_detections_guild_author ON detections(guild_id, author_id);
+CREATE INDEX idx_detections_artwork ON detections(artwork_id);
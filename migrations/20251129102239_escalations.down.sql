@@ -0,0 +1,5 @@
+-- Add down migration script here
+
+DROP TABLE escalations;
+DROP TABLE escalation_rules;
+DROP TYPE ESCALATION_ACTION;
@@ -0,0 +1,37 @@
+-- Add up migration script here
+
+CREATE TYPE ESCALATION_ACTION AS ENUM ('timeout', 'role');
+
+-- actions taken on members who reach a number of detections within a window of days.
+CREATE TABLE escalation_rules (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    threshold SMALLINT NOT NULL,
+    window_days SMALLINT NOT NULL,
+    action ESCALATION_ACTION NOT NULL,
+    timeout_minutes INTEGER,
+    role_id BIGINT,
+    created_by BIGINT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+
+    CHECK ((action = 'timeout') = (timeout_minutes IS NOT NULL)),
+    CHECK ((action = 'role') = (role_id IS NOT NULL))
+);
+
+CREATE INDEX idx_escalation_rules_guild ON escalation_rules(guild_id);
+
+-- audit trail of every escalation applied by the bot.
+CREATE TABLE escalations (
+    id BIGSERIAL PRIMARY KEY,
+    guild_id BIGINT NOT NULL,
+    rule_id BIGINT REFERENCES escalation_rules(id) ON DELETE SET NULL,
+    user_id BIGINT NOT NULL,
+    action ESCALATION_ACTION NOT NULL,
+    detail TEXT NOT NULL,
+    detections SMALLINT NOT NULL,
+    succeeded BOOLEAN NOT NULL,
+    escalated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX idx_escalations_guild_time ON escalations(guild_id, escalated_at DESC);
+CREATE INDEX idx_escalations_rule_user ON escalations(rule_id, user_id, escalated_at DESC);
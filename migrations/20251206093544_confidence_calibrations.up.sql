@@ -0,0 +1,13 @@
+-- Add up migration script here
+
+-- confidence levels calibrated from the stored artworks, for every selection of hashing algorithms
+-- matched with.
+CREATE TABLE confidence_calibrations (
+    selection TEXT PRIMARY KEY,
+    exact_max SMALLINT NOT NULL,
+    very_likely_max SMALLINT NOT NULL,
+    false_match_rates REAL[] NOT NULL,
+    repost_samples INTEGER NOT NULL,
+    unrelated_samples INTEGER NOT NULL,
+    computed_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
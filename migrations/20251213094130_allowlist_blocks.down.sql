@@ -0,0 +1,7 @@
+-- Add down migration script here
+
+ALTER TABLE match_allowlist
+    DROP COLUMN block_0,
+    DROP COLUMN block_1,
+    DROP COLUMN block_2,
+    DROP COLUMN block_3;
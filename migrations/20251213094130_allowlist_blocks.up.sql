@@ -0,0 +1,14 @@
+-- Add up migration script here
+
+-- allowlisted hashes are split into four 16-bit blocks like artwork hashes, so images are looked up
+-- by their fingerprint instead of being compared with every allowlisted hash.
+ALTER TABLE match_allowlist
+    ADD COLUMN block_0 INTEGER GENERATED ALWAYS AS (CASE WHEN length(hash) = 8 THEN (get_byte(hash, 0) << 8) | get_byte(hash, 1) END) STORED,
+    ADD COLUMN block_1 INTEGER GENERATED ALWAYS AS (CASE WHEN length(hash) = 8 THEN (get_byte(hash, 2) << 8) | get_byte(hash, 3) END) STORED,
+    ADD COLUMN block_2 INTEGER GENERATED ALWAYS AS (CASE WHEN length(hash) = 8 THEN (get_byte(hash, 4) << 8) | get_byte(hash, 5) END) STORED,
+    ADD COLUMN block_3 INTEGER GENERATED ALWAYS AS (CASE WHEN length(hash) = 8 THEN (get_byte(hash, 6) << 8) | get_byte(hash, 7) END) STORED;
+
+CREATE INDEX idx_match_allowlist_block_0 ON match_allowlist(algorithm, block_0);
+CREATE INDEX idx_match_allowlist_block_1 ON match_allowlist(algorithm, block_1);
+CREATE INDEX idx_match_allowlist_block_2 ON match_allowlist(algorithm, block_2);
+CREATE INDEX idx_match_allowlist_block_3 ON match_allowlist(algorithm, block_3);
@@ -0,0 +1,196 @@
+use futures::future::BoxFuture;
+use poise::serenity_prelude::{
+    self as serenity, CreateActionRow, CreateAllowedMentions, CreateMessage, Mentionable,
+    ReactionType,
+};
+
+use crate::detection::ImageMatch;
+
+/// Emoji the [`React`] action reacts to messages with.
+const DETECTION_REACTION: &str = "⚠️";
+
+/// Known AI art found in a message, which a guild's response action is taken on.
+pub struct Detection<'a> {
+    pub message: &'a serenity::Message,
+    pub matches: &'a [ImageMatch<'a>],
+    /// Human-readable summary of the matches.
+    pub summary: &'a str,
+    /// Buttons for moderators to report the matches with, sent along with any reply.
+    pub components: Vec<CreateActionRow>,
+}
+
+impl Detection<'_> {
+    /// Replies to the message with the summary of its matches.
+    async fn reply(&self, ctx: &serenity::Context, ping: bool) -> anyhow::Result<()> {
+        self.message
+            .channel_id
+            .send_message(
+                &ctx.http,
+                CreateMessage::new()
+                    .content(self.summary)
+                    .reference_message(self.message)
+                    .allowed_mentions(CreateAllowedMentions::new().replied_user(ping))
+                    .components(self.components.clone()),
+            )
+            .await
+            .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+        Ok(())
+    }
+}
+
+/// Something the bot does about a message containing known AI art, chosen per guild.
+pub trait ResponseAction: Send + Sync {
+    /// The identifier guilds choose this action by, stored in their settings.
+    fn id(&self) -> &'static str;
+
+    /// A short description of what this action does, shown when choosing one.
+    fn description(&self) -> &'static str;
+
+    fn respond<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        detection: &'a Detection<'a>,
+    ) -> BoxFuture<'a, anyhow::Result<()>>;
+}
+
+pub struct ReplyPing;
+pub struct Reply;
+pub struct React;
+pub struct DeleteAndDm;
+pub struct LogOnly;
+
+impl ResponseAction for ReplyPing {
+    fn id(&self) -> &'static str {
+        "reply_ping"
+    }
+
+    fn description(&self) -> &'static str {
+        "reply to the message, pinging its author"
+    }
+
+    fn respond<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        detection: &'a Detection<'a>,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(detection.reply(ctx, true))
+    }
+}
+
+impl ResponseAction for Reply {
+    fn id(&self) -> &'static str {
+        "reply"
+    }
+
+    fn description(&self) -> &'static str {
+        "reply to the message without pinging its author"
+    }
+
+    fn respond<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        detection: &'a Detection<'a>,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(detection.reply(ctx, false))
+    }
+}
+
+impl ResponseAction for React {
+    fn id(&self) -> &'static str {
+        "react"
+    }
+
+    fn description(&self) -> &'static str {
+        "react to the message"
+    }
+
+    fn respond<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        detection: &'a Detection<'a>,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            detection
+                .message
+                .react(
+                    &ctx.http,
+                    ReactionType::Unicode(DETECTION_REACTION.to_string()),
+                )
+                .await
+                .inspect_err(
+                    |e| tracing::error!(err = ?e, "an error occurred when adding reaction"),
+                )?;
+
+            Ok(())
+        })
+    }
+}
+
+impl ResponseAction for DeleteAndDm {
+    fn id(&self) -> &'static str {
+        "delete"
+    }
+
+    fn description(&self) -> &'static str {
+        "delete the message and tell its author why"
+    }
+
+    fn respond<'a>(
+        &'a self,
+        ctx: &'a serenity::Context,
+        detection: &'a Detection<'a>,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async move {
+            let message = detection.message;
+
+            message.delete(&ctx.http).await.inspect_err(
+                |e| tracing::error!(err = ?e, "an error occurred when deleting message"),
+            )?;
+
+            // members can close their DMs, which shouldn't count as the action failing since the
+            // message is gone either way.
+            let _ = message
+                .author
+                .direct_message(
+                    &ctx.http,
+                    CreateMessage::new().content(format!(
+                        "Your message in {} was deleted. {}",
+                        message.channel_id.mention(),
+                        detection.summary
+                    )),
+                )
+                .await
+                .inspect_err(
+                    |e| tracing::warn!(err = ?e, user = %message.author.id, "an error occurred when sending DM"),
+                );
+
+            Ok(())
+        })
+    }
+}
+
+impl ResponseAction for LogOnly {
+    fn id(&self) -> &'static str {
+        "log_only"
+    }
+
+    fn description(&self) -> &'static str {
+        "don't respond, only posting to the log channel"
+    }
+
+    fn respond<'a>(
+        &'a self,
+        _: &'a serenity::Context,
+        _: &'a Detection<'a>,
+    ) -> BoxFuture<'a, anyhow::Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+}
+
+/// Every action a guild can choose to take on known AI art, the first one being the default.
+pub static ACTIONS: &[&dyn ResponseAction] = &[&ReplyPing, &Reply, &React, &DeleteAndDm, &LogOnly];
+
+pub fn get_action(id: &str) -> Option<&'static dyn ResponseAction> {
+    ACTIONS.iter().find(|action| action.id() == id).copied()
+}
@@ -0,0 +1,255 @@
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::Data;
+use crate::detection::LINKED_IMAGE_EXTENSIONS;
+use crate::matching::{self, fetch_artwork_sources};
+use crate::media::decode_frames;
+
+/// How a local file fared when checked for known AI art.
+#[derive(Debug, Serialize)]
+pub struct FileCheck {
+    pub path: PathBuf,
+    /// The artwork the file matched, if any.
+    #[serde(flatten)]
+    pub artwork: Option<MatchedArtwork>,
+    /// Why the file couldn't be checked, if it couldn't.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// A known artwork matched by a local file.
+#[derive(Debug, Serialize)]
+pub struct MatchedArtwork {
+    pub artist: String,
+    pub artwork_id: i64,
+    pub level: String,
+    /// Estimated fraction of unrelated images matching this closely, if it's known.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub false_match_rate: Option<f32>,
+    pub hash_distance: u32,
+    pub transform: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub matched_tiles: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+}
+
+/// Collects every file to check under the given paths. Files given directly are always checked,
+/// while directories are walked for files with the extensions of images and videos.
+fn collect_files(paths: &[PathBuf]) -> Vec<PathBuf> {
+    let mut files = vec![];
+
+    for path in paths {
+        if path.is_dir() {
+            walk_dir(path, &mut files);
+        } else {
+            files.push(path.clone());
+        }
+    }
+
+    files
+}
+
+/// Walks a directory for files with the extensions of images and videos. Symlinked directories
+/// are skipped so links can't loop, and unreadable entries are skipped with a warning.
+fn walk_dir(dir: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!(err = ?e, dir = %dir.display(), "an error occurred when reading directory, skipping");
+            return;
+        }
+    };
+
+    let mut entries: Vec<_> = entries
+        .filter_map(|entry| {
+            entry
+                .and_then(|entry| Ok((entry.path(), entry.file_type()?)))
+                .inspect_err(|e| tracing::warn!(err = ?e, dir = %dir.display(), "an error occurred when reading directory entry, skipping"))
+                .ok()
+        })
+        .collect();
+
+    entries.sort_by(|(left, _), (right, _)| left.cmp(right));
+
+    for (path, file_type) in entries {
+        if file_type.is_dir() {
+            walk_dir(&path, files);
+        } else if file_type.is_symlink() && path.is_dir() {
+            tracing::warn!(dir = %path.display(), "skipping symlinked directory");
+        } else if path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                LINKED_IMAGE_EXTENSIONS.contains(&extension.to_lowercase().as_str())
+            })
+        {
+            files.push(path);
+        }
+    }
+}
+
+/// Decodes and matches a local file against every known artwork at most `radius` bits away.
+async fn check_file(
+    data: &Data,
+    path: &Path,
+    radius: u32,
+) -> anyhow::Result<Option<MatchedArtwork>> {
+    let bytes = tokio::fs::read(path).await?;
+    let frames = decode_frames(&bytes, data.video_decoding.as_ref()).await?;
+
+    let Some(artwork_match) = matching::match_frames(data, &frames, radius).await? else {
+        return Ok(None);
+    };
+
+    let source = fetch_artwork_sources(&data.db, &[artwork_match.artwork_id])
+        .await?
+        .remove(&artwork_match.artwork_id)
+        .map(|source| source.platform.post_url(source.original_id));
+
+    Ok(Some(MatchedArtwork {
+        artist: artwork_match.artist_name,
+        artwork_id: artwork_match.artwork_id,
+        level: artwork_match.level.to_string(),
+        false_match_rate: artwork_match.false_match_rate,
+        hash_distance: artwork_match.hash_distance,
+        transform: artwork_match.transform.to_string(),
+        matched_tiles: artwork_match.matched_tiles,
+        source,
+    }))
+}
+
+/// Prints the checked files as an aligned table, one row per file.
+fn print_table(checks: &[FileCheck]) {
+    let rows: Vec<[String; 6]> = checks
+        .iter()
+        .map(|check| {
+            let path = check.path.display().to_string();
+
+            match (&check.artwork, &check.error) {
+                (Some(artwork), _) => [
+                    path,
+                    artwork.artist.clone(),
+                    match artwork.false_match_rate {
+                        Some(rate) => {
+                            format!(
+                                "{} (est. {:.3}% false matches)",
+                                artwork.level,
+                                rate * 100.0
+                            )
+                        }
+                        None => artwork.level.clone(),
+                    },
+                    match artwork.matched_tiles {
+                        Some(tiles) => format!("{} ({tiles} tiles)", artwork.hash_distance),
+                        None => artwork.hash_distance.to_string(),
+                    },
+                    artwork.transform.clone(),
+                    artwork
+                        .source
+                        .clone()
+                        .unwrap_or_else(|| format!("artwork #{}", artwork.artwork_id)),
+                ],
+                (None, Some(error)) => [
+                    path,
+                    format!("error: {error}"),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                ],
+                (None, None) => [
+                    path,
+                    "-".to_string(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                ],
+            }
+        })
+        .collect();
+
+    let header = ["PATH", "ARTIST", "LEVEL", "DISTANCE", "TRANSFORM", "SOURCE"].map(String::from);
+    let mut widths = header.clone().map(|column| column.chars().count());
+
+    for row in &rows {
+        for (width, column) in widths.iter_mut().zip(row) {
+            *width = (*width).max(column.chars().count());
+        }
+    }
+
+    for row in std::iter::once(&header).chain(&rows) {
+        let line = row
+            .iter()
+            .zip(widths)
+            .map(|(column, width)| format!("{column:width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+
+        println!("{}", line.trim_end());
+    }
+}
+
+/// Checks every image and video under the given paths for known AI art, printing the results as a
+/// table or as JSON. Fails if any file couldn't be checked.
+pub async fn check_task(
+    data: &Data,
+    paths: &[PathBuf],
+    radius: u32,
+    json: bool,
+) -> anyhow::Result<()> {
+    let files = collect_files(paths);
+
+    tracing::info!(files = files.len(), radius, "checking local files");
+
+    let mut checks = Vec::with_capacity(files.len());
+
+    for path in files {
+        let check = match check_file(data, &path, radius).await {
+            Ok(artwork) => FileCheck {
+                path,
+                artwork,
+                error: None,
+            },
+            Err(e) => {
+                tracing::warn!(err = ?e, path = %path.display(), "an error occurred when checking file");
+
+                FileCheck {
+                    path,
+                    artwork: None,
+                    error: Some(e.to_string()),
+                }
+            }
+        };
+
+        checks.push(check);
+    }
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&checks)?);
+    } else {
+        print_table(&checks);
+    }
+
+    let matched = checks
+        .iter()
+        .filter(|check| check.artwork.is_some())
+        .count();
+    let failed = checks.iter().filter(|check| check.error.is_some()).count();
+
+    tracing::info!(
+        files = checks.len(),
+        matched,
+        failed,
+        "finished checking local files"
+    );
+
+    if failed > 0 {
+        anyhow::bail!("{failed} files couldn't be checked");
+    }
+
+    Ok(())
+}
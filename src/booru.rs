@@ -1,12 +1,16 @@
 use reqwest::header::{self, HeaderValue};
 
 use crate::models::danbooru::DanbooruPost;
+use crate::models::database::Platform;
 use crate::models::gelbooru::GelbooruResponse;
 use crate::models::rule34::Rule34Post;
 
 #[derive(Debug, Clone)]
 pub struct BooruClient {
     client: reqwest::Client,
+    /// Client for URLs linked by users, which doesn't follow redirects so links can't lead it
+    /// anywhere it wasn't meant to go.
+    linked_client: reqwest::Client,
     gelbooru_config: GelbooruConfig,
     danbooru_config: DanbooruConfig,
     rule34_config: Rule34Config,
@@ -16,6 +20,9 @@ impl BooruClient {
     pub fn new() -> anyhow::Result<Self> {
         Ok(Self {
             client: reqwest::Client::new(),
+            linked_client: reqwest::Client::builder()
+                .redirect(reqwest::redirect::Policy::none())
+                .build()?,
             gelbooru_config: GelbooruConfig::try_from_env()?,
             danbooru_config: DanbooruConfig::try_from_env()?,
             rule34_config: Rule34Config::try_from_env()?,
@@ -121,6 +128,78 @@ impl BooruClient {
         Ok(response)
     }
 
+    /// Looks up the file URL of a single post, returning `None` if it's gone or has no file.
+    pub async fn get_file_url(
+        &self,
+        platform: &Platform,
+        id: i64,
+    ) -> anyhow::Result<Option<String>> {
+        let search_term = format!("id:{id}");
+
+        let file_url = match platform {
+            Platform::Gelbooru => self
+                .get_gelbooru(&search_term, 0, 1)
+                .await?
+                .posts
+                .and_then(|posts| posts.into_iter().next())
+                .map(|post| post.file_url),
+            Platform::Danbooru => self
+                .get_danbooru(&search_term, 1, "1")
+                .await?
+                .into_iter()
+                .next()
+                .and_then(|post| post.file_url),
+            Platform::Rule34 => self
+                .get_rule34(&search_term, 0, 1)
+                .await?
+                .into_iter()
+                .next()
+                .map(|post| post.file_url),
+        };
+
+        Ok(file_url)
+    }
+
+    /// Downloads a file linked by a user like [`Self::download`], but without following redirects
+    /// and giving up as soon as it turns out to be larger than `max_size` bytes.
+    pub async fn download_limited(
+        &self,
+        url: &str,
+        max_size: usize,
+    ) -> anyhow::Result<bytes::Bytes> {
+        let mut response = self
+            .linked_client
+            .get(url)
+            .header(header::USER_AGENT, HeaderValue::from_static("curl/8.15.0"))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when fetching image"))?;
+
+        if response
+            .content_length()
+            .is_some_and(|length| length > max_size as u64)
+        {
+            anyhow::bail!("file at {url} is larger than {max_size} bytes");
+        }
+
+        let mut bytes = bytes::BytesMut::new();
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when loading bytes"))?
+        {
+            if bytes.len() + chunk.len() > max_size {
+                anyhow::bail!("file at {url} is larger than {max_size} bytes");
+            }
+
+            bytes.extend_from_slice(&chunk);
+        }
+
+        Ok(bytes.freeze())
+    }
+
     pub async fn download(&self, url: &str) -> anyhow::Result<bytes::Bytes> {
         let bytes = self
             .client
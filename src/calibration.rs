@@ -0,0 +1,491 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Cursor;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageBuffer, Rgb};
+use sqlx::{Pool, Postgres};
+
+use crate::Data;
+use crate::hashing::{get_hasher, hamming_distance, preprocess};
+use crate::matching::HashSelection;
+use crate::media::decode_frames;
+use crate::models::database::{FeedbackVerdict, Platform};
+
+/// Number of artworks sampled to measure the distances between unrelated images.
+const UNRELATED_ARTWORKS: i64 = 400;
+/// Number of the sampled artworks downloaded again to measure the distances of their reposts.
+const REPOSTED_ARTWORKS: usize = 100;
+/// Fewest pairs of unrelated artworks confidence levels can be calibrated from.
+const MIN_UNRELATED_PAIRS: usize = 1000;
+/// Highest fraction of unrelated images allowed within the distance of an exact match.
+const EXACT_FALSE_MATCH_RATE: f32 = 0.0001;
+/// Highest fraction of unrelated images allowed within the distance of a very likely match.
+const VERY_LIKELY_FALSE_MATCH_RATE: f32 = 0.001;
+/// Qualities reposts are simulated with, by recompressing artworks as JPEG, downscaled by the
+/// factor paired with each.
+const REPOST_DEGRADATIONS: [(u8, u32); 3] = [(85, 1), (70, 2), (50, 3)];
+
+/// How confident the bot is that an image is a repost of the artwork it matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfidenceLevel {
+    Exact,
+    VeryLikely,
+    Possible,
+}
+
+impl ConfidenceLevel {
+    /// The level as an indefinite noun phrase, as in "an exact match".
+    pub fn as_match(&self) -> &'static str {
+        match self {
+            Self::Exact => "an exact match",
+            Self::VeryLikely => "a very likely match",
+            Self::Possible => "a possible match",
+        }
+    }
+}
+
+impl fmt::Display for ConfidenceLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact => write!(f, "exact"),
+            Self::VeryLikely => write!(f, "very likely"),
+            Self::Possible => write!(f, "possible"),
+        }
+    }
+}
+
+/// Maps the distance of a match to a confidence level, from how close unrelated artworks and
+/// reposts of the same artwork were measured to be.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Calibration {
+    /// Largest distance of an exact match.
+    pub exact_max: u32,
+    /// Largest distance of a very likely match, past which matches are only possible ones.
+    pub very_likely_max: u32,
+    /// Fraction of unrelated artworks at most each distance away from each other, indexed by
+    /// distance. Empty if the confidence levels weren't calibrated.
+    pub false_match_rates: Vec<f32>,
+}
+
+impl Default for Calibration {
+    fn default() -> Self {
+        Self {
+            exact_max: 2,
+            very_likely_max: 8,
+            false_match_rates: vec![],
+        }
+    }
+}
+
+impl Calibration {
+    /// Loads the calibration of a selection of hashing algorithms, falling back to the defaults if
+    /// it was never calibrated.
+    pub async fn load(db: &Pool<Postgres>, selection: &HashSelection) -> anyhow::Result<Self> {
+        let row = sqlx::query!(
+            r#"
+                SELECT exact_max, very_likely_max, false_match_rates
+                FROM confidence_calibrations
+                WHERE selection = $1;
+            "#,
+            selection.key(),
+        )
+        .fetch_optional(db)
+        .await
+        .inspect_err(
+            |e| tracing::error!(err = ?e, "an error occurred when fetching confidence calibration"),
+        )?;
+
+        let Some(row) = row else {
+            tracing::warn!(selection = %selection.key(), "confidence levels aren't calibrated, run `jane-doe calibrate` to calibrate them");
+            return Ok(Self::default());
+        };
+
+        Ok(Self {
+            exact_max: row.exact_max as u32,
+            very_likely_max: row.very_likely_max as u32,
+            false_match_rates: row.false_match_rates,
+        })
+    }
+
+    pub fn level(&self, distance: u32) -> ConfidenceLevel {
+        if distance <= self.exact_max {
+            ConfidenceLevel::Exact
+        } else if distance <= self.very_likely_max {
+            ConfidenceLevel::VeryLikely
+        } else {
+            ConfidenceLevel::Possible
+        }
+    }
+
+    /// The percentage of unrelated artworks farther than `distance` from each other, or the share
+    /// of matching bits if the confidence levels weren't calibrated.
+    pub fn confidence(&self, distance: u32) -> f32 {
+        match self.false_match_rate(distance) {
+            Some(rate) => (1.0 - rate) * 100.0,
+            None => (64 - distance.min(64)) as f32 / 64.0 * 100.0,
+        }
+    }
+
+    /// The fraction of unrelated artworks at most `distance` from each other, if the confidence
+    /// levels were calibrated.
+    pub fn false_match_rate(&self, distance: u32) -> Option<f32> {
+        self.false_match_rates.get(distance as usize).copied()
+    }
+}
+
+/// The calibration matches are scored with, shared with the background task that reloads it.
+#[derive(Debug, Clone, Default)]
+pub struct CalibrationCache {
+    calibration: Arc<RwLock<Calibration>>,
+}
+
+impl CalibrationCache {
+    pub async fn load(db: &Pool<Postgres>, selection: &HashSelection) -> anyhow::Result<Self> {
+        Ok(Self {
+            calibration: Arc::new(RwLock::new(Calibration::load(db, selection).await?)),
+        })
+    }
+
+    pub fn get(&self) -> Calibration {
+        self.calibration
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Loads the calibration again, picking up any `jane-doe calibrate` run since it was loaded.
+    pub async fn reload(
+        &self,
+        db: &Pool<Postgres>,
+        selection: &HashSelection,
+    ) -> anyhow::Result<()> {
+        let calibration = Calibration::load(db, selection).await?;
+
+        *self
+            .calibration
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = calibration;
+
+        Ok(())
+    }
+}
+
+/// Hashes of an artwork for every selected algorithm, keyed by algorithm, one per frame. Only one
+/// variant is kept, either trimmed or untrimmed, so reposts are compared to hashes of their kind.
+#[derive(Debug)]
+struct SampledArtwork {
+    artist_id: Option<i64>,
+    platform: Platform,
+    original_id: i64,
+    /// Random key the artwork was sampled by.
+    sample: f64,
+    trimmed: bool,
+    hashes: HashMap<String, Vec<Vec<u8>>>,
+}
+
+/// The distance between two sets of hashes under a selection of hashing algorithms, between their
+/// closest frames.
+fn selection_distance(
+    selection: &HashSelection,
+    left: &HashMap<String, Vec<Vec<u8>>>,
+    right: &HashMap<String, Vec<Vec<u8>>>,
+) -> Option<u32> {
+    selection.combine(selection.algorithms.iter().map(|algorithm| {
+        let (left, right) = (left.get(*algorithm)?, right.get(*algorithm)?);
+
+        left.iter()
+            .flat_map(|left| right.iter().map(|right| hamming_distance(left, right)))
+            .min()
+    }))
+}
+
+/// Downloads an artwork again and degrades it the way reposts usually are, returning the distance
+/// between every degraded copy and the artwork's stored hashes.
+async fn repost_distances(data: &Data, artwork: &SampledArtwork) -> anyhow::Result<Vec<u32>> {
+    let Some(url) = data
+        .booru
+        .get_file_url(&artwork.platform, artwork.original_id)
+        .await?
+    else {
+        return Ok(vec![]);
+    };
+
+    let bytes = data.booru.download(&url).await?;
+    let frames = decode_frames(&bytes, data.video_decoding.as_ref()).await?;
+
+    let Some(frame) = frames.first() else {
+        return Ok(vec![]);
+    };
+
+    let mut distances = vec![];
+
+    for (quality, downscale) in REPOST_DEGRADATIONS {
+        let repost = degrade(frame, quality, downscale)?;
+        let repost = if artwork.trimmed {
+            preprocess(&repost, data.border_trim).into_owned()
+        } else {
+            repost
+        };
+
+        let hashes: HashMap<String, Vec<Vec<u8>>> = data
+            .hash_selection
+            .algorithms
+            .iter()
+            .filter_map(|id| get_hasher(id))
+            .map(|hasher| (hasher.id().to_string(), vec![hasher.hash(&repost)]))
+            .collect();
+
+        if let Some(distance) = selection_distance(&data.hash_selection, &hashes, &artwork.hashes) {
+            distances.push(distance);
+        }
+    }
+
+    Ok(distances)
+}
+
+/// Downscales an image by a factor and recompresses it as a JPEG of some quality.
+fn degrade(
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    quality: u8,
+    downscale: u32,
+) -> anyhow::Result<ImageBuffer<Rgb<u8>, Vec<u8>>> {
+    let image = DynamicImage::ImageRgb8(image.clone()).resize(
+        (image.width() / downscale).max(1),
+        (image.height() / downscale).max(1),
+        FilterType::Triangle,
+    );
+
+    let mut bytes = vec![];
+    image.write_with_encoder(JpegEncoder::new_with_quality(
+        Cursor::new(&mut bytes),
+        quality,
+    ))?;
+
+    Ok(image::load_from_memory(&bytes)?.to_rgb8())
+}
+
+/// Calibrates the confidence levels of the selected hashing algorithms. Distances between artworks
+/// of different artists, along with detections reported as false positives, make up how close
+/// unrelated images come, while simulated reposts of sampled artworks, along with confirmed
+/// detections, make up how close reposts come.
+pub async fn calibrate_task(data: &Data) -> anyhow::Result<()> {
+    let selection = &data.hash_selection;
+    let (algorithms, versions): (Vec<String>, Vec<i16>) = selection
+        .algorithms
+        .iter()
+        .filter_map(|id| get_hasher(id))
+        .map(|hasher| (hasher.id().to_string(), hasher.version()))
+        .unzip();
+
+    let rows = sqlx::query!(
+        r#"
+            WITH sampled AS (
+                SELECT id, artist_id, platform, original_id, RANDOM() AS sample
+                FROM artist_artworks
+                ORDER BY sample
+                LIMIT $1
+            )
+            SELECT
+                s.id AS "id!",
+                s.artist_id,
+                s.platform AS "platform!: Platform",
+                s.original_id AS "original_id!",
+                s.sample AS "sample!",
+                h.trimmed,
+                h.algorithm,
+                h.hash
+            FROM sampled s
+            JOIN artwork_hashes h ON h.artwork_id = s.id
+            JOIN UNNEST($2::TEXT[], $3::SMALLINT[]) AS current (algorithm, version)
+                ON h.algorithm = current.algorithm AND h.version = current.version
+            ORDER BY s.id, h.trimmed = $4 DESC, h.frame;
+        "#,
+        UNRELATED_ARTWORKS,
+        &algorithms,
+        &versions,
+        data.border_trim.is_some(),
+    )
+    .fetch_all(&data.db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when sampling artworks from database"),
+    )?;
+
+    let mut artworks: HashMap<i64, SampledArtwork> = HashMap::new();
+
+    // the variant incoming images are hashed like comes first, so it's the one kept.
+    for row in rows {
+        let artwork = artworks.entry(row.id).or_insert_with(|| SampledArtwork {
+            artist_id: row.artist_id,
+            platform: row.platform,
+            original_id: row.original_id,
+            sample: row.sample,
+            trimmed: row.trimmed,
+            hashes: HashMap::new(),
+        });
+
+        if row.trimmed != artwork.trimmed {
+            continue;
+        }
+
+        artwork
+            .hashes
+            .entry(row.algorithm)
+            .or_default()
+            .push(row.hash);
+    }
+
+    let mut artworks: Vec<SampledArtwork> = artworks.into_values().collect();
+    // sampled artworks are reposted in the random order they were sampled in.
+    artworks.sort_by(|left, right| left.sample.total_cmp(&right.sample));
+
+    let mut unrelated: Vec<u32> = vec![];
+
+    for (idx, left) in artworks.iter().enumerate() {
+        for right in &artworks[idx + 1..] {
+            if left.artist_id == right.artist_id {
+                continue;
+            }
+
+            if let Some(distance) = selection_distance(selection, &left.hashes, &right.hashes) {
+                unrelated.push(distance);
+            }
+        }
+    }
+
+    let mut reposts: Vec<u32> = vec![];
+
+    let feedback = sqlx::query!(
+        r#"
+            SELECT DISTINCT ON (d.id)
+                d.distance,
+                f.verdict AS "verdict: FeedbackVerdict"
+            FROM detections d
+            JOIN detection_feedback f
+                ON f.message_id = d.message_id AND f.artwork_id = d.artwork_id
+            ORDER BY d.id, f.created_at DESC;
+        "#,
+    )
+    .fetch_all(&data.db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when fetching detection feedback"),
+    )?;
+
+    for row in feedback {
+        match row.verdict {
+            FeedbackVerdict::Confirmed => reposts.push(row.distance as u32),
+            FeedbackVerdict::FalsePositive => unrelated.push(row.distance as u32),
+        }
+    }
+
+    tracing::info!(
+        artworks = artworks.len(),
+        reposted = REPOSTED_ARTWORKS.min(artworks.len()),
+        "simulating reposts of sampled artworks"
+    );
+
+    for chunk in artworks[..REPOSTED_ARTWORKS.min(artworks.len())].chunks(5) {
+        let results =
+            futures::future::join_all(chunk.iter().map(|artwork| repost_distances(data, artwork)))
+                .await;
+
+        for result in results {
+            match result {
+                Ok(distances) => reposts.extend(distances),
+                Err(e) => {
+                    tracing::warn!(err = ?e, "an error occurred when simulating repost, skipping")
+                }
+            }
+        }
+    }
+
+    if unrelated.len() < MIN_UNRELATED_PAIRS {
+        tracing::error!(
+            pairs = unrelated.len(),
+            "not enough unrelated artworks to calibrate confidence levels"
+        );
+        anyhow::bail!(
+            "only {} pairs of unrelated artworks, at least {MIN_UNRELATED_PAIRS} are needed",
+            unrelated.len()
+        );
+    }
+
+    let false_match_rates: Vec<f32> = (0..=64)
+        .map(|distance| {
+            unrelated.iter().filter(|d| **d <= distance).count() as f32 / unrelated.len() as f32
+        })
+        .collect();
+
+    let max_distance_within = |rate: f32| {
+        (0..=64u32)
+            .take_while(|d| false_match_rates[*d as usize] <= rate)
+            .last()
+    };
+
+    let very_likely_max = max_distance_within(VERY_LIKELY_FALSE_MATCH_RATE).unwrap_or(0);
+    let mut exact_max = max_distance_within(EXACT_FALSE_MATCH_RATE)
+        .unwrap_or(0)
+        .min(very_likely_max);
+
+    // exact matches are meant to be copies, not merely images no unrelated one comes close to.
+    reposts.sort_unstable();
+
+    if let Some(median) = reposts.get(reposts.len() / 2) {
+        exact_max = exact_max.min(*median);
+    }
+
+    let repost_rate = |max: u32| {
+        reposts.iter().filter(|d| **d <= max).count() as f32 / reposts.len().max(1) as f32
+    };
+
+    tracing::info!(
+        selection = %selection.key(),
+        unrelated = unrelated.len(),
+        reposts = reposts.len(),
+        exact_max,
+        very_likely_max,
+        exact_reposts = repost_rate(exact_max),
+        very_likely_reposts = repost_rate(very_likely_max),
+        "calibrated confidence levels, running bots pick them up after their next daily scrape"
+    );
+
+    sqlx::query!(
+        r#"
+            INSERT INTO
+                confidence_calibrations (
+                    selection,
+                    exact_max,
+                    very_likely_max,
+                    false_match_rates,
+                    repost_samples,
+                    unrelated_samples
+                )
+            VALUES
+                ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (selection) DO UPDATE SET
+                exact_max = excluded.exact_max,
+                very_likely_max = excluded.very_likely_max,
+                false_match_rates = excluded.false_match_rates,
+                repost_samples = excluded.repost_samples,
+                unrelated_samples = excluded.unrelated_samples,
+                computed_at = NOW();
+        "#,
+        selection.key(),
+        exact_max as i16,
+        very_likely_max as i16,
+        &false_match_rates,
+        reposts.len() as i32,
+        unrelated.len() as i32,
+    )
+    .execute(&data.db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when storing confidence calibration"),
+    )?;
+
+    Ok(())
+}
@@ -1,5 +1,10 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand};
 
+use crate::matching::MATCH_RADIUS;
+use crate::settings::MAX_MATCH_RADIUS;
+
 #[derive(Debug, Parser)]
 #[command(author, version, about, long_about = None)]
 #[command(propagate_version = true)]
@@ -18,4 +23,26 @@ pub enum Command {
     Start,
     /// Run the scrape task.
     Scrape,
+    /// Rehash every artwork hashed by an outdated version of a hashing algorithm.
+    Rehash,
+    /// Recompute the confidence levels from the stored artworks.
+    Calibrate,
+    /// Check local images, videos or directories of them for known AI art.
+    Check {
+        /// Files or directories to check, directories being walked for images and videos.
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Match artworks at most this many bits away.
+        #[arg(
+            long,
+            default_value_t = MATCH_RADIUS,
+            value_parser = clap::value_parser!(u32).range(..=MAX_MATCH_RADIUS as i64)
+        )]
+        radius: u32,
+
+        /// Print the results as JSON instead of a table.
+        #[arg(long)]
+        json: bool,
+    },
 }
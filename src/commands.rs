@@ -1,10 +1,255 @@
-use crate::Context;
+use std::time::Duration;
+
+use poise::serenity_prelude::{
+    ButtonStyle, CreateActionRow, CreateButton, CreateEmbed, CreateEmbedFooter,
+    CreateInteractionResponse, CreateInteractionResponseMessage, collector,
+};
+
+use crate::{Context, Error};
+
+/// How long the invoking user has to confirm a destructive operation.
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(30);
+/// How long pages can be turned after the last time they were.
+const PAGINATION_TIMEOUT: Duration = Duration::from_secs(60);
 
 pub mod alias;
 pub mod artist;
+pub mod check;
+pub mod config;
+pub mod curator;
+pub mod escalation;
 pub mod help;
+pub mod history;
 pub mod status;
 
 pub(crate) fn get_bot_avatar(ctx: Context<'_>) -> String {
     ctx.cache().current_user().avatar_url().unwrap_or_default()
 }
+
+/// Asks the invoking user to confirm a destructive operation with buttons, returning whether they
+/// did before the confirmation timed out.
+pub(crate) async fn confirm(ctx: Context<'_>, prompt: String) -> Result<bool, Error> {
+    let ctx_id = ctx.id();
+    let confirm_id = format!("{ctx_id}confirm");
+    let cancel_id = format!("{ctx_id}cancel");
+
+    let msg = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(prompt)
+                .components(vec![CreateActionRow::Buttons(vec![
+                    CreateButton::new(&confirm_id)
+                        .label("confirm")
+                        .style(ButtonStyle::Danger),
+                    CreateButton::new(&cancel_id)
+                        .label("cancel")
+                        .style(ButtonStyle::Secondary),
+                ])]),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    let author_id = ctx.author().id;
+
+    while let Some(press) = collector::ComponentInteractionCollector::new(ctx)
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .timeout(CONFIRMATION_TIMEOUT)
+        .await
+    {
+        if press.user.id != author_id {
+            press
+                .create_response(
+                    ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("you cannot interact with another user's invoked command!")
+                            .ephemeral(true),
+                    ),
+                )
+                .await
+                .inspect_err(
+                    |e| tracing::error!(err = ?e, "an error occurred when creating response"),
+                )?;
+
+            continue;
+        }
+
+        let confirmed = press.data.custom_id == confirm_id;
+
+        press
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .content(if confirmed {
+                            "confirmed."
+                        } else {
+                            "cancelled."
+                        })
+                        .components(vec![]),
+                ),
+            )
+            .await
+            .inspect_err(
+                |e| tracing::error!(err = ?e, "an error occurred when creating response"),
+            )?;
+
+        return Ok(confirmed);
+    }
+
+    msg.edit(
+        ctx,
+        poise::CreateReply::default()
+            .content("timed out, nothing was changed.")
+            .components(vec![]),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when editing message"))?;
+
+    Ok(false)
+}
+
+/// Sends pages of an embed only visible to the invoking user, along with buttons for them to turn the
+/// pages with. The pages must not be empty.
+pub(crate) async fn paginate(ctx: Context<'_>, title: &str, pages: &[String]) -> Result<(), Error> {
+    let ctx_id = ctx.id();
+    let author_id = ctx.author().id;
+    let first_id = format!("{ctx_id}first");
+    let last_id = format!("{ctx_id}last");
+    let prev_id = format!("{ctx_id}prev");
+    let next_id = format!("{ctx_id}next");
+
+    let page = |current_page: usize| {
+        let embed = CreateEmbed::default()
+            .title(title)
+            .description(&pages[current_page])
+            .footer(CreateEmbedFooter::new(format!(
+                "page {}/{}",
+                current_page + 1,
+                pages.len(),
+            )));
+        let buttons = vec![CreateActionRow::Buttons(vec![
+            CreateButton::new(&first_id)
+                .emoji('⏮')
+                .disabled(current_page == 0),
+            CreateButton::new(&prev_id)
+                .emoji('◀')
+                .disabled(current_page == 0),
+            CreateButton::new(&next_id)
+                .emoji('▶')
+                .disabled(current_page == pages.len() - 1),
+            CreateButton::new(&last_id)
+                .emoji('⏭')
+                .disabled(current_page == pages.len() - 1),
+        ])];
+
+        (embed, buttons)
+    };
+
+    let mut current_page: usize = 0;
+    let (embed, buttons) = page(current_page);
+
+    let msg = ctx
+        .send(
+            poise::CreateReply::default()
+                .embed(embed)
+                .components(buttons)
+                .ephemeral(true),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    while let Some(press) = collector::ComponentInteractionCollector::new(ctx)
+        .filter(move |press| press.data.custom_id.starts_with(&ctx_id.to_string()))
+        .timeout(PAGINATION_TIMEOUT)
+        .await
+    {
+        if press.user.id != author_id {
+            press
+                .create_response(
+                    ctx,
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content("you cannot interact with another user's invoked command!")
+                            .ephemeral(true),
+                    ),
+                )
+                .await
+                .inspect_err(
+                    |e| tracing::error!(err = ?e, "an error occurred when creating response"),
+                )?;
+
+            continue;
+        }
+
+        if press.data.custom_id == prev_id {
+            current_page = current_page.saturating_sub(1);
+        } else if press.data.custom_id == next_id {
+            current_page = (current_page + 1).min(pages.len() - 1);
+        } else if press.data.custom_id == first_id {
+            current_page = 0;
+        } else if press.data.custom_id == last_id {
+            current_page = pages.len() - 1;
+        } else {
+            continue;
+        }
+
+        let (embed, buttons) = page(current_page);
+
+        press
+            .create_response(
+                ctx,
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .embed(embed)
+                        .components(buttons),
+                ),
+            )
+            .await
+            .inspect_err(
+                |e| tracing::error!(err = ?e, "an error occurred when creating response"),
+            )?;
+    }
+
+    msg.edit(ctx, poise::CreateReply::default().components(vec![]))
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when editing message"))?;
+
+    Ok(())
+}
+
+/// Autocompletes the names of known AI artists containing what's been typed so far.
+pub(crate) async fn autocomplete_artist(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    sqlx::query_scalar!(
+        r#"
+            SELECT name
+            FROM ai_artists
+            WHERE STRPOS(LOWER(name), LOWER($1)) > 0
+            ORDER BY name
+            LIMIT 25;
+        "#,
+        partial,
+    )
+    .fetch_all(&ctx.data().db)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when autocompleting artists"))
+    .unwrap_or_default()
+}
+
+/// Autocompletes the aliases of known AI artists containing what's been typed so far.
+pub(crate) async fn autocomplete_alias(ctx: Context<'_>, partial: &str) -> Vec<String> {
+    sqlx::query_scalar!(
+        r#"
+            SELECT alias
+            FROM ai_artist_aliases
+            WHERE STRPOS(LOWER(alias), LOWER($1)) > 0
+            ORDER BY alias
+            LIMIT 25;
+        "#,
+        partial,
+    )
+    .fetch_all(&ctx.data().db)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when autocompleting aliases"))
+    .unwrap_or_default()
+}
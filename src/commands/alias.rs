@@ -1,23 +1,35 @@
 use poise::serenity_prelude::*;
 
+use crate::commands::artist::delete_artist;
+use crate::commands::{autocomplete_alias, autocomplete_artist, confirm};
 use crate::models::database::AiArtist;
+use crate::permissions::curator_check;
 use crate::{Context, Error};
 
+/// manage aliases of known AI artists.
 #[tracing::instrument(skip_all)]
 #[poise::command(
     prefix_command,
+    slash_command,
     guild_only,
     aliases("aliases"),
-    subcommands("add", "list", "delete"),
+    subcommands("add", "list", "delete", "delete_alias"),
     subcommand_required
 )]
 pub async fn alias(_: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// add an alias for a known AI artist, which gets scraped alongside their name.
 #[tracing::instrument(skip_all)]
-#[poise::command(prefix_command, guild_only)]
-pub async fn add(ctx: Context<'_>, artist: String, alias: String) -> Result<(), Error> {
+#[poise::command(prefix_command, slash_command, guild_only, check = "curator_check")]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "name of the artist"]
+    #[autocomplete = "autocomplete_artist"]
+    artist: String,
+    #[description = "alias of the artist, as tagged on imageboards"] alias: String,
+) -> Result<(), Error> {
     let existing_ai_artist = sqlx::query!(
         r#"
             SELECT
@@ -57,7 +69,8 @@ pub async fn add(ctx: Context<'_>, artist: String, alias: String) -> Result<(),
                 if e.as_database_error().unwrap().is_unique_violation() {
                     ctx.send(
                         poise::CreateReply::default()
-                            .content(format!("alias \"{alias}\" already exists.")),
+                            .content(format!("alias \"{alias}\" already exists."))
+                            .ephemeral(true),
                     )
                     .await
                     .inspect_err(
@@ -78,7 +91,8 @@ pub async fn add(ctx: Context<'_>, artist: String, alias: String) -> Result<(),
         None => {
             ctx.send(
                 poise::CreateReply::default()
-                    .content(format!("artist \"{artist}\" does not exist.")),
+                    .content(format!("artist \"{artist}\" does not exist."))
+                    .ephemeral(true),
             )
             .await
             .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
@@ -88,7 +102,8 @@ pub async fn add(ctx: Context<'_>, artist: String, alias: String) -> Result<(),
     Ok(())
 }
 
-#[poise::command(prefix_command)]
+/// list every known AI artist along with their aliases.
+#[poise::command(prefix_command, slash_command)]
 #[tracing::instrument(skip_all)]
 pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
     let msg = ctx
@@ -128,7 +143,6 @@ pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
                 .unwrap_or_default();
 
             AiArtist {
-                id: row.id,
                 name: row.name,
                 aliases,
             }
@@ -291,61 +305,29 @@ pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// delete a known AI artist along with their aliases and artworks, the same as `artist delete`.
 #[tracing::instrument(skip(ctx))]
-#[poise::command(prefix_command)]
-pub async fn delete(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error> {
-    let existing_artist = sqlx::query!(
-        r#"
-            SELECT
-                id AS "id!",
-                name
-            FROM ai_artists
-            WHERE name = $1;
-        "#,
-        name
-    )
-    .fetch_optional(&ctx.data().db)
-    .await
-    .inspect_err(|e| {
-        tracing::error!(err = ?e, name = %name, "an error occurred when fetching artist");
-    })?;
-
-    match existing_artist {
-        Some(_) => {
-            sqlx::query!(
-                r#"
-                    DELETE FROM ai_artists
-                    WHERE name = $1;
-                "#,
-                name
-            )
-            .execute(&ctx.data().db)
-            .await
-            .inspect_err(
-                |e| tracing::error!(err = ?e, name = %name, "an error occurred when deleting artist")
-            )?;
-
-            ctx.send(poise::CreateReply::default().content(format!("deleted artist \"{name}\".")))
-                .await
-                .inspect_err(
-                    |e| tracing::error!(err = ?e, "an error occurred when sending reply"),
-                )?;
-        }
-        None => {
-            ctx.send(
-                poise::CreateReply::default().content(format!("artist \"{name}\" does not exist.")),
-            )
-            .await
-            .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
-        }
-    }
-
-    Ok(())
+#[poise::command(prefix_command, slash_command, check = "curator_check")]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "name of the artist"]
+    #[autocomplete = "autocomplete_artist"]
+    #[rest]
+    name: String,
+) -> Result<(), Error> {
+    delete_artist(ctx, name).await
 }
 
+/// delete an alias of a known AI artist.
 #[tracing::instrument(skip(ctx))]
-#[poise::command(prefix_command)]
-pub async fn delete_alias(ctx: Context<'_>, #[rest] alias: String) -> Result<(), Error> {
+#[poise::command(prefix_command, slash_command, check = "curator_check")]
+pub async fn delete_alias(
+    ctx: Context<'_>,
+    #[description = "alias to delete"]
+    #[autocomplete = "autocomplete_alias"]
+    #[rest]
+    alias: String,
+) -> Result<(), Error> {
     let existing_alias = sqlx::query!(
         r#"
             SELECT
@@ -363,6 +345,10 @@ pub async fn delete_alias(ctx: Context<'_>, #[rest] alias: String) -> Result<(),
 
     match existing_alias {
         Some(_) => {
+            if !confirm(ctx, format!("delete alias \"{alias}\"?")).await? {
+                return Ok(());
+            }
+
             sqlx::query!(
                 r#"
                     DELETE FROM ai_artist_aliases
@@ -384,7 +370,9 @@ pub async fn delete_alias(ctx: Context<'_>, #[rest] alias: String) -> Result<(),
         }
         None => {
             ctx.send(
-                poise::CreateReply::default().content(format!("alias \"{alias}\" does not exist.")),
+                poise::CreateReply::default()
+                    .content(format!("alias \"{alias}\" does not exist."))
+                    .ephemeral(true),
             )
             .await
             .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
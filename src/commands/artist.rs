@@ -1,11 +1,15 @@
 use poise::serenity_prelude::*;
 
+use crate::commands::{autocomplete_artist, confirm};
 use crate::models::database::AiArtist;
+use crate::permissions::curator_check;
 use crate::{Context, Error};
 
+/// manage known AI artists.
 #[tracing::instrument(skip_all)]
 #[poise::command(
     prefix_command,
+    slash_command,
     guild_only,
     aliases("artists"),
     subcommands("add", "list", "delete"),
@@ -15,9 +19,15 @@ pub async fn artist(_: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// add a known AI artist, whose artworks get scraped from then on.
 #[tracing::instrument(skip_all)]
-#[poise::command(prefix_command, guild_only)]
-pub async fn add(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error> {
+#[poise::command(prefix_command, slash_command, guild_only, check = "curator_check")]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "name of the artist, as tagged on imageboards"]
+    #[rest]
+    name: String,
+) -> Result<(), Error> {
     let result = sqlx::query!(
         r#"
             INSERT INTO
@@ -37,7 +47,8 @@ pub async fn add(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error> {
         if e.as_database_error().unwrap().is_unique_violation() {
             ctx.send(
                 poise::CreateReply::default()
-                    .content(format!("artist with name \"{name}\" already exists.")),
+                    .content(format!("artist with name \"{name}\" already exists."))
+                    .ephemeral(true),
             )
             .await
             .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
@@ -53,7 +64,8 @@ pub async fn add(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error> {
     Ok(())
 }
 
-#[poise::command(prefix_command)]
+/// list every known AI artist along with their aliases.
+#[poise::command(prefix_command, slash_command)]
 #[tracing::instrument(skip_all)]
 pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
     let msg = ctx
@@ -93,7 +105,6 @@ pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
                 .unwrap_or_default();
 
             AiArtist {
-                id: row.id,
                 name: row.name,
                 aliases,
             }
@@ -256,9 +267,21 @@ pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// delete a known AI artist along with their aliases and artworks.
 #[tracing::instrument(skip(ctx))]
-#[poise::command(prefix_command)]
-pub async fn delete(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error> {
+#[poise::command(prefix_command, slash_command, check = "curator_check")]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "name of the artist"]
+    #[autocomplete = "autocomplete_artist"]
+    #[rest]
+    name: String,
+) -> Result<(), Error> {
+    delete_artist(ctx, name).await
+}
+
+/// Deletes a known AI artist after confirming it, shared by `artist delete` and `alias delete`.
+pub async fn delete_artist(ctx: Context<'_>, name: String) -> Result<(), Error> {
     let existing_artist = sqlx::query!(
         r#"
             SELECT
@@ -276,7 +299,29 @@ pub async fn delete(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error>
     })?;
 
     match existing_artist {
-        Some(_) => {
+        Some(artist) => {
+            let artworks = sqlx::query_scalar!(
+                r#"
+                    SELECT COUNT(*) AS "count!"
+                    FROM artist_artworks
+                    WHERE artist_id = $1;
+                "#,
+                artist.id
+            )
+            .fetch_one(&ctx.data().db)
+            .await
+            .inspect_err(|e| {
+                tracing::error!(err = ?e, name = %name, "an error occurred when counting artworks");
+            })?;
+
+            let prompt = format!(
+                "delete artist \"{name}\" along with their aliases and {artworks} artworks? this cannot be undone."
+            );
+
+            if !confirm(ctx, prompt).await? {
+                return Ok(());
+            }
+
             sqlx::query!(
                 r#"
                     DELETE FROM ai_artists
@@ -290,6 +335,8 @@ pub async fn delete(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error>
                 |e| tracing::error!(err = ?e, name = %name, "an error occurred when deleting artist")
             )?;
 
+            ctx.data().index.remove_artist(artist.id);
+
             ctx.send(poise::CreateReply::default().content(format!("deleted artist \"{name}\".")))
                 .await
                 .inspect_err(
@@ -298,7 +345,9 @@ pub async fn delete(ctx: Context<'_>, #[rest] name: String) -> Result<(), Error>
         }
         None => {
             ctx.send(
-                poise::CreateReply::default().content(format!("artist \"{name}\" does not exist.")),
+                poise::CreateReply::default()
+                    .content(format!("artist \"{name}\" does not exist."))
+                    .ephemeral(true),
             )
             .await
             .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
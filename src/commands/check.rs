@@ -0,0 +1,221 @@
+use poise::serenity_prelude::*;
+
+use crate::commands::get_bot_avatar;
+use crate::detection::{
+    ImageMatch, MessageImage, close_artists_note, false_match_note, is_discord_media, match_note,
+    support_note,
+};
+use crate::hashing::{ImageHashes, Transform, preprocess};
+use crate::matching::{self, MATCH_RADIUS};
+use crate::permissions::moderator_check;
+use crate::{Context, Error};
+
+/// Artworks up to this many bits away are reported by `check`, which is further than
+/// [`MATCH_RADIUS`] by default to help with investigating borderline cases.
+const REPORT_RADIUS: u32 = 20;
+
+/// check an image for known AI art, reporting the closest known artworks.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "moderator_check")]
+pub async fn check(
+    ctx: Context<'_>,
+    #[description = "image to check"] image: Option<Attachment>,
+    #[description = "URL of the image to check, hosted by Discord"] url: Option<String>,
+    #[description = "number of closest artworks to report, 5 by default"]
+    #[min = 1]
+    #[max = 20]
+    count: Option<u32>,
+) -> Result<(), Error> {
+    let image = match (image, url) {
+        (Some(attachment), _) => MessageImage::Attachment(attachment),
+        (None, Some(url)) if is_discord_media(&url) => MessageImage::Url(url),
+        (None, Some(_)) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content("only images hosted by Discord can be checked by URL, attach the image instead.")
+                    .ephemeral(true),
+            )
+            .await
+            .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+            return Ok(());
+        }
+        (None, None) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content("attach an image or give its URL to check it.")
+                    .ephemeral(true),
+            )
+            .await
+            .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+            return Ok(());
+        }
+    };
+
+    ctx.defer_ephemeral()
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when deferring reply"))?;
+
+    let Ok(frames) = image.download_frames(ctx.data()).await else {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(format!("couldn't load `{}` as an image.", image.name()))
+                .ephemeral(true),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+        return Ok(());
+    };
+
+    let data = ctx.data();
+    let count = count.unwrap_or(5).clamp(1, 20) as usize;
+    let radius = match_radius(ctx).await?;
+
+    let closest = matching::closest_artworks(data, &frames, REPORT_RADIUS, count).await?;
+    let artwork_ids: Vec<i64> = closest
+        .iter()
+        .map(|(_, _, artwork)| artwork.artwork_id)
+        .collect();
+    let sources = matching::fetch_artwork_sources(&data.db, &artwork_ids).await?;
+
+    let mut report = String::new();
+
+    for (idx, (distance, transform, artwork)) in closest.iter().enumerate() {
+        let Some(source) = sources.get(&artwork.artwork_id) else {
+            continue;
+        };
+
+        report += &format!(
+            "{}. **{}**, [{} #{}]({}), distance {distance}{}{}\n",
+            idx + 1,
+            source.artist_name,
+            source.platform,
+            source.original_id,
+            source.platform.post_url(source.original_id),
+            match transform {
+                Transform::Original => String::new(),
+                transform => format!(" when {transform}"),
+            },
+            if *distance <= radius { " (match)" } else { "" },
+        );
+    }
+
+    if report.is_empty() {
+        report = format!("no known artworks within {REPORT_RADIUS} bits.");
+    }
+
+    let mut hashes_str = String::new();
+
+    if let Some(frame) = frames.first() {
+        let trimmed = data.border_trim.is_some();
+        let hashes = ImageHashes::compute(&preprocess(frame, data.border_trim), trimmed);
+
+        for (key, hash) in hashes.iter() {
+            let hex: String = hash.iter().map(|byte| format!("{byte:02x}")).collect();
+            hashes_str += &format!("{:<6}{hex}\n", key.algorithm);
+        }
+    }
+
+    let mut embed = CreateEmbed::default()
+        .title("image check")
+        .description(report)
+        .field("hashes", format!("```\n{hashes_str}```"), false)
+        .thumbnail(get_bot_avatar(ctx));
+
+    if frames.len() > 1 {
+        embed = embed.footer(CreateEmbedFooter::new(format!(
+            "checked {} frames, hashes are of the first one.",
+            frames.len()
+        )));
+    }
+
+    ctx.send(poise::CreateReply::default().embed(embed).ephemeral(true))
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+/// check every image of a message for known AI art.
+#[tracing::instrument(skip_all)]
+#[poise::command(
+    context_menu_command = "Check this image",
+    guild_only,
+    check = "moderator_check",
+    default_member_permissions = "MANAGE_MESSAGES"
+)]
+pub async fn check_message(ctx: Context<'_>, message: Message) -> Result<(), Error> {
+    ctx.defer_ephemeral()
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when deferring reply"))?;
+
+    let images = MessageImage::collect(ctx.http(), ctx.data(), &message).await;
+
+    if images.is_empty() {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("this message has no images to check.")
+                .ephemeral(true),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+        return Ok(());
+    }
+
+    let radius = match_radius(ctx).await?;
+
+    let mut breakdown = String::new();
+
+    for (idx, image) in images.iter().enumerate() {
+        let result = match image.check(ctx.data(), radius).await {
+            Ok(Some(ImageMatch { artwork_match, .. })) => format!(
+                "known AI art by **{}**, {}{}{}.{}\n-# artwork #{}, hash distance {}{}",
+                artwork_match.artist_name,
+                artwork_match.level.as_match(),
+                match_note(&artwork_match),
+                support_note(&artwork_match),
+                close_artists_note(&artwork_match),
+                artwork_match.artwork_id,
+                artwork_match.hash_distance,
+                false_match_note(&artwork_match),
+            ),
+            Ok(None) => "no known AI art found.".to_string(),
+            Err(_) => "couldn't be checked.".to_string(),
+        };
+
+        breakdown += &format!("{}. `{}`: {result}\n", idx + 1, image.name());
+    }
+
+    ctx.send(
+        poise::CreateReply::default()
+            .embed(
+                CreateEmbed::default()
+                    .title("image check")
+                    .url(message.link())
+                    .description(breakdown)
+                    .thumbnail(get_bot_avatar(ctx)),
+            )
+            .ephemeral(true),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+/// The matching radius of the guild the command was invoked in.
+async fn match_radius(ctx: Context<'_>) -> anyhow::Result<u32> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(MATCH_RADIUS);
+    };
+
+    Ok(ctx
+        .data()
+        .settings
+        .get(&ctx.data().db, guild_id)
+        .await?
+        .match_radius)
+}
@@ -0,0 +1,382 @@
+use poise::serenity_prelude::*;
+
+use crate::actions::{ACTIONS, get_action};
+use crate::permissions::{admin_check, manager_check};
+use crate::settings::{GuildSettings, MAX_MATCH_RADIUS};
+use crate::{Context, Error};
+
+/// configure how the bot behaves in this server.
+#[tracing::instrument(skip_all)]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    check = "manager_check",
+    subcommands(
+        "show",
+        "enable",
+        "disable",
+        "threshold",
+        "response",
+        "exclude",
+        "include",
+        "log_channel",
+        "add_manager",
+        "remove_manager"
+    ),
+    subcommand_required
+)]
+pub async fn config(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// show the current settings of this server.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn show(ctx: Context<'_>) -> Result<(), Error> {
+    let settings = guild_settings(ctx).await?;
+
+    let mention_all = |mentions: Vec<String>| {
+        if mentions.is_empty() {
+            "none".to_string()
+        } else {
+            mentions.join(", ")
+        }
+    };
+
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("server settings")
+                .field(
+                    "detection",
+                    if settings.enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    },
+                    true,
+                )
+                .field(
+                    "matching threshold",
+                    format!("{} bits", settings.match_radius),
+                    true,
+                )
+                .field("response", settings.response_action, true)
+                .field(
+                    "excluded channels",
+                    mention_all(
+                        settings
+                            .excluded_channels
+                            .iter()
+                            .map(|channel| channel.mention().to_string())
+                            .collect(),
+                    ),
+                    false,
+                )
+                .field(
+                    "excluded roles",
+                    mention_all(
+                        settings
+                            .excluded_roles
+                            .iter()
+                            .map(|role| role.mention().to_string())
+                            .collect(),
+                    ),
+                    false,
+                )
+                .field(
+                    "log channel",
+                    settings
+                        .log_channel
+                        .map_or("none".to_string(), |channel| channel.mention().to_string()),
+                    false,
+                )
+                .field(
+                    "manager roles",
+                    mention_all(
+                        settings
+                            .manager_roles
+                            .iter()
+                            .map(|role| role.mention().to_string())
+                            .collect(),
+                    ),
+                    false,
+                ),
+        ),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+/// enable checking messages for known AI art in this server.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn enable(ctx: Context<'_>) -> Result<(), Error> {
+    update_settings(ctx, |settings| settings.enabled = true).await?;
+    reply(ctx, "enabled detection in this server.".to_string()).await
+}
+
+/// disable checking messages for known AI art in this server.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn disable(ctx: Context<'_>) -> Result<(), Error> {
+    update_settings(ctx, |settings| settings.enabled = false).await?;
+    reply(ctx, "disabled detection in this server.".to_string()).await
+}
+
+/// set how many bits an image's hash can differ from an artwork's to match it.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn threshold(
+    ctx: Context<'_>,
+    #[description = "maximum hash distance, lower is stricter"]
+    #[min = 0]
+    // poise only takes literals as limits, so this one is asserted to match MAX_MATCH_RADIUS.
+    #[max = 20]
+    bits: u32,
+) -> Result<(), Error> {
+    const _: () = assert!(MAX_MATCH_RADIUS == 20);
+
+    if bits > MAX_MATCH_RADIUS {
+        ctx.send(
+            poise::CreateReply::default()
+                .content(format!(
+                    "the matching threshold can be at most {MAX_MATCH_RADIUS} bits."
+                ))
+                .ephemeral(true),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+        return Ok(());
+    }
+
+    update_settings(ctx, |settings| settings.match_radius = bits).await?;
+    reply(ctx, format!("set the matching threshold to {bits} bits.")).await
+}
+
+/// set the action the bot takes on messages containing known AI art.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn response(
+    ctx: Context<'_>,
+    #[description = "action to take"]
+    #[autocomplete = "autocomplete_action"]
+    action: String,
+) -> Result<(), Error> {
+    let Some(action) = get_action(&action) else {
+        let actions: Vec<&str> = ACTIONS.iter().map(|action| action.id()).collect();
+
+        ctx.send(
+            poise::CreateReply::default()
+                .content(format!(
+                    "unknown action \"{action}\", expected one of {}.",
+                    actions.join(", ")
+                ))
+                .ephemeral(true),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+        return Ok(());
+    };
+
+    update_settings(ctx, |settings| settings.response_action = action.id()).await?;
+    reply(
+        ctx,
+        format!(
+            "set the response to \"{}\": {}.",
+            action.id(),
+            action.description()
+        ),
+    )
+    .await
+}
+
+/// stop checking messages in a channel, or from members with a role.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn exclude(
+    ctx: Context<'_>,
+    #[description = "channel to exclude"] channel: Option<GuildChannel>,
+    #[description = "role to exclude"] role: Option<Role>,
+) -> Result<(), Error> {
+    let (channel, role) = (channel.map(|c| c.id), role.map(|r| r.id));
+
+    if channel.is_none() && role.is_none() {
+        return nothing_given(ctx, "exclude").await;
+    }
+
+    update_settings(ctx, |settings| {
+        if let Some(channel) = channel
+            && !settings.excluded_channels.contains(&channel)
+        {
+            settings.excluded_channels.push(channel);
+        }
+
+        if let Some(role) = role
+            && !settings.excluded_roles.contains(&role)
+        {
+            settings.excluded_roles.push(role);
+        }
+    })
+    .await?;
+
+    reply(ctx, format!("excluded {}.", mentions(channel, role))).await
+}
+
+/// resume checking messages in an excluded channel, or from members with an excluded role.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn include(
+    ctx: Context<'_>,
+    #[description = "channel to include again"] channel: Option<GuildChannel>,
+    #[description = "role to include again"] role: Option<Role>,
+) -> Result<(), Error> {
+    let (channel, role) = (channel.map(|c| c.id), role.map(|r| r.id));
+
+    if channel.is_none() && role.is_none() {
+        return nothing_given(ctx, "include again").await;
+    }
+
+    update_settings(ctx, |settings| {
+        settings
+            .excluded_channels
+            .retain(|excluded| Some(*excluded) != channel);
+        settings
+            .excluded_roles
+            .retain(|excluded| Some(*excluded) != role);
+    })
+    .await?;
+
+    reply(ctx, format!("included {} again.", mentions(channel, role))).await
+}
+
+/// set the channel detections are logged to, or stop logging them.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn log_channel(
+    ctx: Context<'_>,
+    #[description = "channel to log to, leave empty to stop logging"] channel: Option<GuildChannel>,
+) -> Result<(), Error> {
+    let channel = channel.map(|c| c.id);
+
+    update_settings(ctx, |settings| settings.log_channel = channel).await?;
+
+    match channel {
+        Some(channel) => reply(ctx, format!("logging detections to {}.", channel.mention())).await,
+        None => reply(ctx, "stopped logging detections.".to_string()).await,
+    }
+}
+
+/// Autocompletes the response actions whose identifier contains what's been typed so far.
+async fn autocomplete_action(_: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    ACTIONS
+        .iter()
+        .filter(|action| action.id().contains(partial))
+        .map(|action| {
+            AutocompleteChoice::new(
+                format!("{}: {}", action.id(), action.description()),
+                action.id(),
+            )
+        })
+        .collect()
+}
+
+/// let members with a role manage the bot in this server.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "admin_check")]
+pub async fn add_manager(
+    ctx: Context<'_>,
+    #[description = "role to let manage the bot"] role: Role,
+) -> Result<(), Error> {
+    update_settings(ctx, |settings| {
+        if !settings.manager_roles.contains(&role.id) {
+            settings.manager_roles.push(role.id);
+        }
+    })
+    .await?;
+
+    reply(
+        ctx,
+        format!("members with {} can now manage the bot.", role.mention()),
+    )
+    .await
+}
+
+/// stop letting members with a role manage the bot in this server.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "admin_check")]
+pub async fn remove_manager(
+    ctx: Context<'_>,
+    #[description = "role to stop letting manage the bot"] role: Role,
+) -> Result<(), Error> {
+    update_settings(ctx, |settings| {
+        settings.manager_roles.retain(|manager| *manager != role.id)
+    })
+    .await?;
+
+    reply(
+        ctx,
+        format!(
+            "members with {} can no longer manage the bot.",
+            role.mention()
+        ),
+    )
+    .await
+}
+
+async fn guild_settings(ctx: Context<'_>) -> anyhow::Result<GuildSettings> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("guild settings outside of a guild"))?;
+
+    ctx.data().settings.get(&ctx.data().db, guild_id).await
+}
+
+async fn update_settings(
+    ctx: Context<'_>,
+    change: impl FnOnce(&mut GuildSettings),
+) -> anyhow::Result<GuildSettings> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("guild settings outside of a guild"))?;
+
+    ctx.data()
+        .settings
+        .update(&ctx.data().db, guild_id, change)
+        .await
+}
+
+async fn reply(ctx: Context<'_>, content: String) -> Result<(), Error> {
+    ctx.send(poise::CreateReply::default().content(content))
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+/// Tells the invoker to give a channel or a role to `verb`, without touching the settings.
+async fn nothing_given(ctx: Context<'_>, verb: &str) -> Result<(), Error> {
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!("give a channel or a role to {verb}."))
+            .ephemeral(true),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+fn mentions(channel: Option<ChannelId>, role: Option<RoleId>) -> String {
+    match (channel, role) {
+        (Some(channel), Some(role)) => format!("{} and {}", channel.mention(), role.mention()),
+        (Some(channel), None) => channel.mention().to_string(),
+        (None, Some(role)) => role.mention().to_string(),
+        (None, None) => "nothing".to_string(),
+    }
+}
@@ -0,0 +1,127 @@
+use poise::serenity_prelude::*;
+
+use crate::{Context, Error};
+
+/// manage the global curators, who can manage known AI artists everywhere.
+#[tracing::instrument(skip_all)]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    owners_only,
+    aliases("curators"),
+    subcommands("add", "remove", "list"),
+    subcommand_required
+)]
+pub async fn curator(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// make a user a global curator.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn add(
+    ctx: Context<'_>,
+    #[description = "user to make a curator"] user: User,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+            INSERT INTO
+                curators (user_id, added_by)
+            VALUES
+                ($1, $2)
+            ON CONFLICT (user_id) DO NOTHING;
+        "#,
+        user.id.get() as i64,
+        ctx.author().id.get() as i64,
+    )
+    .execute(&ctx.data().db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, user = %user.id, "an error occurred when adding curator"),
+    )?;
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(format!("{} is now a curator.", user.mention()))
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+/// stop a user from being a global curator.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "curator to remove"] user: User,
+) -> Result<(), Error> {
+    let result = sqlx::query!(
+        r#"
+            DELETE FROM curators
+            WHERE user_id = $1;
+        "#,
+        user.id.get() as i64,
+    )
+    .execute(&ctx.data().db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, user = %user.id, "an error occurred when removing curator"),
+    )?;
+
+    let content = if result.rows_affected() == 0 {
+        format!("{} is not a curator.", user.mention())
+    } else {
+        format!("{} is no longer a curator.", user.mention())
+    };
+
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .allowed_mentions(CreateAllowedMentions::new()),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+/// list every global curator.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, owners_only)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let curators = sqlx::query_scalar!(
+        r#"
+            SELECT user_id
+            FROM curators
+            ORDER BY created_at;
+        "#,
+    )
+    .fetch_all(&ctx.data().db)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when fetching curators"))?;
+
+    let description = if curators.is_empty() {
+        "there are no curators.".to_string()
+    } else {
+        curators
+            .into_iter()
+            .map(|id| format!("- {}", UserId::new(id as u64).mention()))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("curators")
+                .description(description),
+        ),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
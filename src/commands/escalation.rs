@@ -0,0 +1,332 @@
+use poise::serenity_prelude::*;
+
+use crate::commands::paginate;
+use crate::escalation::fetch_rules;
+use crate::models::database::{EscalationAction, EscalationRule};
+use crate::permissions::{can_give_role, can_time_out, manager_check};
+use crate::{Context, Error};
+
+/// Longest timeout Discord allows, in minutes.
+const MAX_TIMEOUT_MINUTES: u32 = 28 * 24 * 60;
+/// Most detections a rule can take to escalate.
+const MAX_DETECTIONS: u16 = 100;
+/// Most days a rule can count detections over.
+const MAX_WINDOW_DAYS: u16 = 365;
+/// Number of escalations listed per page of the audit trail.
+const ESCALATIONS_PER_PAGE: usize = 10;
+/// Maximum number of escalations looked up in the audit trail.
+const MAX_ESCALATIONS: i64 = 1000;
+
+/// manage what the bot does to members who repeatedly post known AI art.
+#[tracing::instrument(skip_all)]
+#[poise::command(
+    prefix_command,
+    slash_command,
+    guild_only,
+    check = "manager_check",
+    aliases("escalations"),
+    subcommands("add_timeout", "add_role", "list", "remove", "log"),
+    subcommand_required
+)]
+pub async fn escalation(_: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// time members out after a number of detections within some days.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn add_timeout(
+    ctx: Context<'_>,
+    #[description = "number of detections"]
+    #[min = 1]
+    #[max = 100]
+    detections: u16,
+    #[description = "number of days the detections are counted over"]
+    #[min = 1]
+    #[max = 365]
+    days: u16,
+    #[description = "length of the timeout, in minutes"]
+    #[min = 1]
+    #[max = 40320]
+    minutes: u32,
+) -> Result<(), Error> {
+    if !can_time_out(ctx).await {
+        return reply(
+            ctx,
+            "you need to be able to time out members and have a role higher than mine to add timeouts."
+                .to_string(),
+        )
+        .await;
+    }
+
+    if !(1..=MAX_TIMEOUT_MINUTES).contains(&minutes) {
+        return reply(
+            ctx,
+            format!("timeouts have to last between 1 and {MAX_TIMEOUT_MINUTES} minutes."),
+        )
+        .await;
+    }
+
+    add_rule(
+        ctx,
+        detections,
+        days,
+        EscalationAction::Timeout,
+        Some(i32::try_from(minutes)?),
+        None,
+    )
+    .await
+}
+
+/// give members a role after a number of detections within some days.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn add_role(
+    ctx: Context<'_>,
+    #[description = "number of detections"]
+    #[min = 1]
+    #[max = 100]
+    detections: u16,
+    #[description = "number of days the detections are counted over"]
+    #[min = 1]
+    #[max = 365]
+    days: u16,
+    #[description = "role to give"] role: Role,
+) -> Result<(), Error> {
+    if !can_give_role(ctx, &role).await {
+        return reply(
+            ctx,
+            format!(
+                "you need to be able to manage roles and have a role higher than {} to give it.",
+                role.mention()
+            ),
+        )
+        .await;
+    }
+
+    add_rule(
+        ctx,
+        detections,
+        days,
+        EscalationAction::Role,
+        None,
+        Some(role.id.get() as i64),
+    )
+    .await
+}
+
+/// list the escalation rules of this server.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("escalation rules outside of a guild"))?;
+
+    let rules = fetch_rules(&ctx.data().db, guild_id).await?;
+
+    let description = if rules.is_empty() {
+        "there are no escalation rules.".to_string()
+    } else {
+        rules
+            .iter()
+            .map(|rule| format!("`#{}` {rule}", rule.id))
+            .collect::<Vec<_>>()
+            .join("\n")
+    };
+
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::default()
+                .title("escalation rules")
+                .description(description),
+        ),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+/// remove an escalation rule of this server.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "ID of the rule, as listed"] id: i64,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("escalation rules outside of a guild"))?;
+
+    let result = sqlx::query!(
+        r#"
+            DELETE FROM escalation_rules
+            WHERE id = $1 AND guild_id = $2;
+        "#,
+        id,
+        guild_id.get() as i64,
+    )
+    .execute(&ctx.data().db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, rule_id = id, "an error occurred when removing escalation rule"),
+    )?;
+
+    if result.rows_affected() == 0 {
+        return reply(ctx, format!("escalation rule #{id} does not exist.")).await;
+    }
+
+    ctx.send(poise::CreateReply::default().content(format!("removed escalation rule #{id}.")))
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+/// show every escalation the bot applied in this server.
+#[tracing::instrument(skip_all)]
+#[poise::command(prefix_command, slash_command, guild_only, check = "manager_check")]
+pub async fn log(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("escalations outside of a guild"))?;
+
+    let escalations = sqlx::query!(
+        r#"
+            SELECT
+                rule_id,
+                user_id,
+                detail,
+                detections,
+                succeeded,
+                escalated_at
+            FROM escalations
+            WHERE guild_id = $1
+            ORDER BY escalated_at DESC
+            LIMIT $2;
+        "#,
+        guild_id.get() as i64,
+        MAX_ESCALATIONS,
+    )
+    .fetch_all(&ctx.data().db)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when fetching escalations"))?;
+
+    if escalations.is_empty() {
+        return reply(ctx, "no escalations found.".to_string()).await;
+    }
+
+    let pages: Vec<String> = escalations
+        .chunks(ESCALATIONS_PER_PAGE)
+        .map(|chunk| {
+            chunk
+                .iter()
+                .map(|escalation| {
+                    format!(
+                        "<t:{}:f> {} {} after {} detections{}{}\n",
+                        escalation.escalated_at.timestamp(),
+                        UserId::new(escalation.user_id as u64).mention(),
+                        escalation.detail,
+                        escalation.detections,
+                        escalation
+                            .rule_id
+                            .map_or(String::new(), |id| format!(" (rule #{id})")),
+                        if escalation.succeeded { "" } else { ", failed" },
+                    )
+                })
+                .collect()
+        })
+        .collect();
+
+    paginate(ctx, "escalation log", &pages).await
+}
+
+async fn add_rule(
+    ctx: Context<'_>,
+    detections: u16,
+    days: u16,
+    action: EscalationAction,
+    timeout_minutes: Option<i32>,
+    role_id: Option<i64>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("escalation rules outside of a guild"))?;
+
+    // the limits of parameters are only enforced by Discord for slash commands, not prefix ones.
+    if !(1..=MAX_DETECTIONS).contains(&detections) {
+        return reply(
+            ctx,
+            format!("rules have to take between 1 and {MAX_DETECTIONS} detections."),
+        )
+        .await;
+    }
+
+    if !(1..=MAX_WINDOW_DAYS).contains(&days) {
+        return reply(
+            ctx,
+            format!("detections have to be counted over between 1 and {MAX_WINDOW_DAYS} days."),
+        )
+        .await;
+    }
+
+    let (threshold, window_days) = (i16::try_from(detections)?, i16::try_from(days)?);
+
+    let id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO
+                escalation_rules (
+                    guild_id,
+                    threshold,
+                    window_days,
+                    action,
+                    timeout_minutes,
+                    role_id,
+                    created_by
+                )
+            VALUES
+                ($1, $2, $3, $4, $5, $6, $7)
+            RETURNING id;
+        "#,
+        guild_id.get() as i64,
+        threshold,
+        window_days,
+        action as EscalationAction,
+        timeout_minutes,
+        role_id,
+        ctx.author().id.get() as i64,
+    )
+    .fetch_one(&ctx.data().db)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when adding escalation rule"))?;
+
+    let rule = EscalationRule {
+        id,
+        threshold,
+        window_days,
+        action,
+        timeout_minutes,
+        role_id,
+    };
+
+    ctx.send(
+        poise::CreateReply::default().content(format!("added escalation rule `#{id}`: {rule}.")),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
+
+async fn reply(ctx: Context<'_>, content: String) -> Result<(), Error> {
+    ctx.send(
+        poise::CreateReply::default()
+            .content(content)
+            .ephemeral(true),
+    )
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
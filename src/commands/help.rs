@@ -2,13 +2,15 @@ use crate::{Context, Error};
 
 /// print the list of commands and their usage
 #[tracing::instrument(skip(ctx))]
-#[poise::command(prefix_command)]
+#[poise::command(prefix_command, slash_command)]
 pub async fn help(
     ctx: Context<'_>,
-    #[description = "specific command to show help about"] command: Option<String>,
+    #[description = "specific command to show help about"]
+    #[autocomplete = "poise::builtins::autocomplete_command"]
+    command: Option<String>,
 ) -> Result<(), Error> {
     let config = poise::builtins::HelpConfiguration {
-        extra_text_at_bottom: "Type `j!help command` for more info on a command.",
+        extra_text_at_bottom: "Type `j!help command` or `/help command` for more info on a command.",
         ..Default::default()
     };
 
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, NaiveDate, TimeDelta, Utc};
+use poise::serenity_prelude::*;
+
+use crate::commands::{autocomplete_artist, paginate};
+use crate::permissions::moderator_check;
+use crate::{Context, Error};
+
+/// Maximum number of detections looked up at once.
+const MAX_DETECTIONS: i64 = 1000;
+/// Number of detections listed per page.
+const DETECTIONS_PER_PAGE: usize = 10;
+/// Number of users with the most detections listed on every page.
+const TOP_OFFENDERS: usize = 3;
+
+/// look up past detections in this server, by user, artist or time range.
+#[tracing::instrument(skip(ctx))]
+#[poise::command(prefix_command, slash_command, guild_only, check = "moderator_check")]
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "user who posted the images"] user: Option<User>,
+    #[description = "name of the matched artist"]
+    #[autocomplete = "autocomplete_artist"]
+    artist: Option<String>,
+    #[description = "first day to look up, as in 2025-01-31"] since: Option<String>,
+    #[description = "last day to look up, as in 2025-01-31"] until: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or_else(|| anyhow::anyhow!("history outside of a guild"))?;
+
+    let (since, until) = match (
+        since.as_deref().map(parse_day).transpose(),
+        until.as_deref().map(parse_day).transpose(),
+    ) {
+        (Ok(since), Ok(until)) => (since, until.map(|until| until + TimeDelta::days(1))),
+        (Err(day), _) | (_, Err(day)) => {
+            ctx.send(
+                poise::CreateReply::default()
+                    .content(format!(
+                        "cannot parse `{day}` as a day, expected a date like 2025-01-31."
+                    ))
+                    .ephemeral(true),
+            )
+            .await
+            .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+            return Ok(());
+        }
+    };
+
+    let detections = sqlx::query!(
+        r#"
+            SELECT
+                d.channel_id,
+                d.message_id,
+                d.author_id,
+                d.distance,
+                d.action,
+                d.action_succeeded,
+                d.detected_at,
+                a.name AS "artist_name?"
+            FROM detections d
+            LEFT JOIN artist_artworks aw ON d.artwork_id = aw.id
+            LEFT JOIN ai_artists a ON aw.artist_id = a.id
+            WHERE d.guild_id = $1
+                AND ($2::BIGINT IS NULL OR d.author_id = $2)
+                AND ($3::TEXT IS NULL OR a.name = $3)
+                AND ($4::TIMESTAMPTZ IS NULL OR d.detected_at >= $4)
+                AND ($5::TIMESTAMPTZ IS NULL OR d.detected_at < $5)
+            ORDER BY d.detected_at DESC
+            LIMIT $6;
+        "#,
+        guild_id.get() as i64,
+        user.as_ref().map(|user| user.id.get() as i64),
+        artist,
+        since,
+        until,
+        MAX_DETECTIONS,
+    )
+    .fetch_all(&ctx.data().db)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when fetching detections"))?;
+
+    if detections.is_empty() {
+        ctx.send(
+            poise::CreateReply::default()
+                .content("no detections found.")
+                .ephemeral(true),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+        return Ok(());
+    }
+
+    let mut offenders: HashMap<i64, usize> = HashMap::new();
+
+    for detection in &detections {
+        *offenders.entry(detection.author_id).or_default() += 1;
+    }
+
+    let mut offenders: Vec<(i64, usize)> = offenders.into_iter().collect();
+    offenders.sort_by_key(|(author_id, count)| (std::cmp::Reverse(*count), *author_id));
+
+    let mut summary = format!("{} detections", detections.len());
+
+    if user.is_none() {
+        let top: Vec<String> = offenders
+            .iter()
+            .take(TOP_OFFENDERS)
+            .map(|(author_id, count)| {
+                format!("{} ({count})", UserId::new(*author_id as u64).mention())
+            })
+            .collect();
+
+        summary += &format!(", most by {}", top.join(", "));
+    }
+
+    let pages: Vec<String> = detections
+        .chunks(DETECTIONS_PER_PAGE)
+        .enumerate()
+        .map(|(page, chunk)| {
+            let mut page_str = format!("{summary}\n\n");
+
+            for (idx, detection) in chunk.iter().enumerate() {
+                let link = MessageId::new(detection.message_id as u64)
+                    .link(ChannelId::new(detection.channel_id as u64), Some(guild_id));
+
+                page_str += &format!(
+                    "{}. <t:{}:f> {} in {}: **{}** (distance {}), {}{} [jump]({link})\n",
+                    idx + 1 + page * DETECTIONS_PER_PAGE,
+                    detection.detected_at.timestamp(),
+                    UserId::new(detection.author_id as u64).mention(),
+                    ChannelId::new(detection.channel_id as u64).mention(),
+                    detection.artist_name.as_deref().unwrap_or("deleted artist"),
+                    detection.distance,
+                    detection.action,
+                    if detection.action_succeeded {
+                        ""
+                    } else {
+                        " (failed)"
+                    },
+                );
+            }
+
+            page_str
+        })
+        .collect();
+
+    paginate(ctx, "detection history", &pages).await
+}
+
+/// Parses a day like `2025-01-31` into the moment it starts, in UTC.
+fn parse_day(day: &str) -> Result<DateTime<Utc>, &str> {
+    NaiveDate::parse_from_str(day, "%Y-%m-%d")
+        .map(|date| date.and_time(Default::default()).and_utc())
+        .map_err(|_| day)
+}
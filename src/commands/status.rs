@@ -9,7 +9,7 @@ use crate::{Context, Error};
 
 /// get the bot's status.
 #[tracing::instrument(skip_all)]
-#[poise::command(prefix_command)]
+#[poise::command(prefix_command, slash_command)]
 pub async fn status(ctx: Context<'_>) -> Result<(), Error> {
     ctx.send(poise::CreateReply::default().embed(
         serenity::CreateEmbed::new()
@@ -0,0 +1,429 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use image::{ImageBuffer, Rgb};
+use poise::serenity_prelude::{
+    Attachment, Embed, Http, LightMethod, Message, MessageId, MessageReferenceKind, Request, Route,
+    StickerFormatType, StickerItem,
+};
+use reqwest::Url;
+
+use crate::Data;
+use crate::feedback;
+use crate::hashing::Transform;
+use crate::matching;
+use crate::media::decode_frames;
+use crate::models::database::ArtworkMatch;
+use crate::models::discord::{MessageSnapshot, RawMessage};
+
+/// Extensions of the files linked in messages that are checked as images.
+pub const LINKED_IMAGE_EXTENSIONS: [&str; 8] =
+    ["png", "jpg", "jpeg", "gif", "webp", "avif", "mp4", "webm"];
+/// Maximum number of images linked in a single message that get checked.
+const MAX_LINKED_IMAGES: usize = 5;
+/// Maximum size of attachments and images downloaded by URL, so that huge files can't stall the
+/// bot.
+const MAX_IMAGE_SIZE: usize = 20 * 1024 * 1024;
+/// Hosts of Discord's CDN and media proxy, the only hosts images are downloaded from by URL, so
+/// members can't make the bot request arbitrary addresses.
+const DISCORD_MEDIA_HOSTS: [&str; 4] = [
+    "cdn.discordapp.com",
+    "media.discordapp.net",
+    "images-ext-1.discordapp.net",
+    "images-ext-2.discordapp.net",
+];
+
+/// Artists whose closest artwork is at most this many bits farther than the matched one are
+/// reported as close too.
+const CLOSE_ARTIST_MARGIN: u32 = 2;
+
+/// Number of recent messages whose checked images are remembered.
+const MAX_REMEMBERED_MESSAGES: usize = 10_000;
+
+/// An image in a message that can be checked for known AI art.
+#[derive(Debug, Clone)]
+pub enum MessageImage {
+    Attachment(Attachment),
+    /// An image linked by URL, such as the image or thumbnail of an embed, always hosted by
+    /// Discord's CDN or media proxy.
+    Url(String),
+}
+
+impl MessageImage {
+    /// Collects every image of a message worth checking, along with those of the messages it
+    /// forwards.
+    pub async fn collect(http: &Http, data: &Data, message: &Message) -> Vec<Self> {
+        let mut images = Self::from_parts(
+            data,
+            &message.attachments,
+            &message.content,
+            &message.embeds,
+            &message.sticker_items,
+        );
+
+        if message
+            .message_reference
+            .as_ref()
+            .is_some_and(|reference| reference.kind == MessageReferenceKind::Forward)
+        {
+            // serenity doesn't parse the snapshots of forwarded messages yet, so they're fetched
+            // separately.
+            let Ok(snapshots) = fetch_snapshots(http, message).await else {
+                return images;
+            };
+
+            for snapshot in snapshots {
+                let snapshot = snapshot.message;
+
+                images.extend(Self::from_parts(
+                    data,
+                    &snapshot.attachments,
+                    &snapshot.content,
+                    &snapshot.embeds,
+                    &snapshot.sticker_items,
+                ));
+            }
+        }
+
+        images
+    }
+
+    /// Collects every attachment with dimensions, meaning images and videos, small enough to
+    /// download, every image linked in the content, every embedded image and, if enabled, every
+    /// sticker of a message.
+    fn from_parts(
+        data: &Data,
+        attachments: &[Attachment],
+        content: &str,
+        embeds: &[Embed],
+        stickers: &[StickerItem],
+    ) -> Vec<Self> {
+        let attachments = attachments
+            .iter()
+            .filter(|attachment| attachment.dimensions().is_some())
+            .filter(|attachment| attachment.size as usize <= MAX_IMAGE_SIZE)
+            .cloned()
+            .map(Self::Attachment);
+
+        let links = linked_images(content);
+        let embeds = Self::from_embeds(embeds, &links);
+
+        // lottie stickers have no image URL, being animations described in JSON.
+        let stickers = stickers
+            .iter()
+            .filter(|_| data.sticker_checking)
+            .filter(|sticker| sticker.format_type != StickerFormatType::Lottie)
+            .filter_map(StickerItem::image_url)
+            .map(Self::Url);
+
+        attachments
+            .chain(links.into_iter().map(Self::Url))
+            .chain(embeds)
+            .chain(stickers)
+            .collect()
+    }
+
+    /// Collects the image or thumbnail of every embed by its URL on Discord's media proxy,
+    /// skipping embeds of images in `links`, which are checked by their link instead.
+    fn from_embeds(embeds: &[Embed], links: &[String]) -> Vec<Self> {
+        let links: HashSet<&str> = links.iter().map(String::as_str).collect();
+
+        embeds
+            .iter()
+            .filter(|embed| {
+                let urls = [
+                    embed.url.as_deref(),
+                    embed.image.as_ref().map(|image| image.url.as_str()),
+                    embed
+                        .thumbnail
+                        .as_ref()
+                        .map(|thumbnail| thumbnail.url.as_str()),
+                ];
+
+                !urls.into_iter().flatten().any(|url| links.contains(url))
+            })
+            .filter_map(|embed| {
+                let image = embed
+                    .image
+                    .as_ref()
+                    .and_then(|image| image.proxy_url.as_ref());
+                let thumbnail = embed
+                    .thumbnail
+                    .as_ref()
+                    .and_then(|thumbnail| thumbnail.proxy_url.as_ref());
+
+                image
+                    .or(thumbnail)
+                    .filter(|url| is_discord_media(url))
+                    .cloned()
+                    .map(Self::Url)
+            })
+            .collect()
+    }
+
+    /// Identifies the image among the images of its message.
+    fn key(&self) -> String {
+        match self {
+            Self::Attachment(attachment) => attachment.id.to_string(),
+            Self::Url(url) => url.clone(),
+        }
+    }
+
+    /// A short, human readable name of the image.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Attachment(attachment) => &attachment.filename,
+            Self::Url(url) => url,
+        }
+    }
+
+    /// Where the image can be viewed.
+    pub fn url(&self) -> &str {
+        match self {
+            Self::Attachment(attachment) => &attachment.url,
+            Self::Url(url) => url,
+        }
+    }
+
+    /// Downloads the image, unless it's too large.
+    async fn download(&self, data: &Data) -> anyhow::Result<Vec<u8>> {
+        let bytes = match self {
+            Self::Attachment(attachment) if attachment.size as usize > MAX_IMAGE_SIZE => {
+                anyhow::bail!(
+                    "attachment {} is larger than {MAX_IMAGE_SIZE} bytes",
+                    attachment.filename
+                )
+            }
+            Self::Attachment(attachment) => attachment.download().await.inspect_err(
+                |e| tracing::error!(err = ?e, "an error occurred when downloading attachment"),
+            )?,
+            Self::Url(url) if !is_discord_media(url) => {
+                anyhow::bail!("{url} isn't hosted by Discord")
+            }
+            Self::Url(url) => data
+                .booru
+                .download_limited(url, MAX_IMAGE_SIZE)
+                .await?
+                .to_vec(),
+        };
+
+        Ok(bytes)
+    }
+
+    /// Downloads and decodes every frame of the image worth hashing.
+    pub async fn download_frames(
+        &self,
+        data: &Data,
+    ) -> anyhow::Result<Vec<ImageBuffer<Rgb<u8>, Vec<u8>>>> {
+        self.decode(data, &self.download(data).await?).await
+    }
+
+    /// Decodes every frame of the downloaded image worth hashing.
+    async fn decode(
+        &self,
+        data: &Data,
+        bytes: &[u8],
+    ) -> anyhow::Result<Vec<ImageBuffer<Rgb<u8>, Vec<u8>>>> {
+        let frames = decode_frames(bytes, data.video_decoding.as_ref())
+            .await
+            .inspect_err(|e| tracing::error!(err = ?e, image = %self.name(), "an error occurred when loading image"))?;
+
+        Ok(frames)
+    }
+
+    /// Downloads, decodes and matches the image against every known artwork at most `radius` bits
+    /// away.
+    pub async fn check(&self, data: &Data, radius: u32) -> anyhow::Result<Option<ImageMatch<'_>>> {
+        let bytes = self.download(data).await?;
+        let frames = self.decode(data, &bytes).await?;
+
+        let Some(artwork_match) = matching::match_frames(data, &frames, radius).await? else {
+            return Ok(None);
+        };
+
+        Ok(Some(ImageMatch {
+            image: self,
+            artwork_match,
+            fingerprint: feedback::fingerprint(data, &frames[0]),
+            bytes,
+        }))
+    }
+}
+
+/// Known AI art matched by an image of a message.
+pub struct ImageMatch<'a> {
+    pub image: &'a MessageImage,
+    pub artwork_match: ArtworkMatch,
+    /// Hash the image is allowlisted by if it's reported as a false positive.
+    pub fingerprint: Vec<u8>,
+    /// The downloaded image, kept as evidence in case the message gets deleted.
+    pub bytes: Vec<u8>,
+}
+
+/// Reads `STICKER_CHECKING`, which is disabled by default.
+pub fn sticker_checking_from_env() -> anyhow::Result<bool> {
+    match std::env::var("STICKER_CHECKING").as_deref() {
+        Err(_) | Ok("false") => Ok(false),
+        Ok("true") => Ok(true),
+        Ok(other) => {
+            tracing::error!(value = %other, "an invalid STICKER_CHECKING was specified");
+            anyhow::bail!("invalid STICKER_CHECKING \"{other}\", expected \"true\" or \"false\"")
+        }
+    }
+}
+
+/// Remembers which images of recent messages were already checked, so that messages are only
+/// checked for their new images when they get updated, and the same image isn't reported twice.
+#[derive(Debug, Clone, Default)]
+pub struct CheckedImages {
+    inner: Arc<Mutex<CheckedImagesInner>>,
+}
+
+#[derive(Debug, Default)]
+struct CheckedImagesInner {
+    images: HashMap<MessageId, HashSet<String>>,
+    /// Remembered messages, oldest first.
+    messages: VecDeque<MessageId>,
+}
+
+impl CheckedImages {
+    /// Keeps only the images of a message that weren't checked yet, remembering them as checked.
+    pub fn retain_unchecked(
+        &self,
+        message_id: MessageId,
+        mut images: Vec<MessageImage>,
+    ) -> Vec<MessageImage> {
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if !inner.images.contains_key(&message_id) {
+            inner.messages.push_back(message_id);
+
+            if inner.messages.len() > MAX_REMEMBERED_MESSAGES
+                && let Some(oldest) = inner.messages.pop_front()
+            {
+                inner.images.remove(&oldest);
+            }
+        }
+
+        let checked = inner.images.entry(message_id).or_default();
+        images.retain(|image| checked.insert(image.key()));
+
+        images
+    }
+
+    /// Forgets that an image of a message was checked, so it's checked again if the message gets
+    /// updated, as when checking it failed.
+    pub fn forget(&self, message_id: MessageId, image: &MessageImage) {
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(checked) = inner.images.get_mut(&message_id) {
+            checked.remove(&image.key());
+        }
+    }
+}
+
+async fn fetch_snapshots(http: &Http, message: &Message) -> anyhow::Result<Vec<MessageSnapshot>> {
+    let response = http
+        .request(Request::new(
+            Route::ChannelMessage {
+                channel_id: message.channel_id,
+                message_id: message.id,
+            },
+            LightMethod::Get,
+        ))
+        .await
+        .inspect_err(
+            |e| tracing::error!(err = ?e, "an error occurred when fetching forwarded message"),
+        )?
+        .bytes()
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when loading bytes"))?;
+
+    let message: RawMessage = serde_json::from_slice(&response).inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when decoding forwarded message"),
+    )?;
+
+    Ok(message.message_snapshots)
+}
+
+/// Describes how an artwork was matched, if it wasn't matched as is.
+pub fn match_note(artwork_match: &ArtworkMatch) -> String {
+    match (artwork_match.transform, artwork_match.matched_tiles) {
+        (_, Some(tiles)) => format!(" ({tiles} matching tiles)"),
+        (Transform::Original, None) => String::new(),
+        (transform, None) => format!(" (matched when {transform})"),
+    }
+}
+
+/// Notes the estimated rate of unrelated images matching as closely, if it's known.
+pub fn false_match_note(artwork_match: &ArtworkMatch) -> String {
+    match artwork_match.false_match_rate {
+        Some(rate) => format!(" (estimated false-match rate {:.3}%)", rate * 100.0),
+        None => String::new(),
+    }
+}
+
+/// Notes how many of the matched artist's artworks are among the closest ones, if several are.
+pub fn support_note(artwork_match: &ArtworkMatch) -> String {
+    match artwork_match.artists.first() {
+        Some(artist) if artist.artworks > 1 => {
+            format!(", matching {} of their artworks", artist.artworks)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Notes every artist whose closest artwork is about as close as the matched one, when there are
+/// several of them and the match can't tell them apart.
+pub fn close_artists_note(artwork_match: &ArtworkMatch) -> String {
+    let close: Vec<String> = artwork_match
+        .artists
+        .iter()
+        .filter(|artist| artist.hash_distance <= artwork_match.hash_distance + CLOSE_ARTIST_MARGIN)
+        .map(|artist| {
+            format!(
+                "{} ({} artworks, {} bits)",
+                artist.artist_name, artist.artworks, artist.hash_distance
+            )
+        })
+        .collect();
+
+    if close.len() < 2 {
+        return String::new();
+    }
+
+    format!(" Several artists are close though: {}.", close.join(", "))
+}
+
+/// Whether a URL points to Discord's CDN or media proxy over HTTPS.
+pub fn is_discord_media(url: &str) -> bool {
+    Url::parse(url).is_ok_and(|url| {
+        url.scheme() == "https"
+            && url.port().is_none()
+            && url
+                .host_str()
+                .is_some_and(|host| DISCORD_MEDIA_HOSTS.contains(&host))
+    })
+}
+
+/// Finds every link to an image on Discord's CDN in the content of a message, by the extension of
+/// the linked file. Images linked from anywhere else are checked by their embed once Discord
+/// unfurls them, through its media proxy.
+fn linked_images(content: &str) -> Vec<String> {
+    content
+        .split_whitespace()
+        // markdown links, as in `[text](url)`
+        .map(|word| word.split_once("](").map_or(word, |(_, url)| url))
+        .map(|word| word.trim_end_matches(['.', ',', '!', '?']))
+        .map(|word| word.trim_matches(['<', '>', '(', ')']))
+        .filter(|word| is_discord_media(word))
+        .filter_map(|word| Url::parse(word).ok())
+        .filter(|url| {
+            url.path().rsplit_once('.').is_some_and(|(_, extension)| {
+                LINKED_IMAGE_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+            })
+        })
+        .map(String::from)
+        .take(MAX_LINKED_IMAGES)
+        .collect()
+}
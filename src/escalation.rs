@@ -0,0 +1,191 @@
+use chrono::{TimeDelta, Utc};
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, EditMember, GuildId, Mentionable, RoleId, Timestamp, UserId,
+};
+use sqlx::{Pool, Postgres};
+
+use crate::Data;
+use crate::models::database::{EscalationAction, EscalationRule};
+
+/// Reason shown in the guild's audit log for escalations.
+const AUDIT_LOG_REASON: &str = "repeatedly posted known AI art";
+
+/// Fetches every escalation rule of a guild, in the order they were added.
+pub async fn fetch_rules(
+    db: &Pool<Postgres>,
+    guild_id: GuildId,
+) -> anyhow::Result<Vec<EscalationRule>> {
+    let rules = sqlx::query_as!(
+        EscalationRule,
+        r#"
+            SELECT
+                id,
+                threshold,
+                window_days,
+                action AS "action: EscalationAction",
+                timeout_minutes,
+                role_id
+            FROM escalation_rules
+            WHERE guild_id = $1
+            ORDER BY id;
+        "#,
+        guild_id.get() as i64,
+    )
+    .fetch_all(db)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, guild_id = %guild_id, "an error occurred when fetching escalation rules"))?;
+
+    Ok(rules)
+}
+
+/// Applies every escalation rule of a guild whose threshold a member reached with their recorded
+/// detections. A rule is applied at most once per window to the same member, and every escalation
+/// is recorded in the audit trail, as well as posted to the log channel if there is one.
+pub async fn escalate(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+    log_channel: Option<ChannelId>,
+) -> anyhow::Result<()> {
+    for rule in fetch_rules(&data.db, guild_id).await? {
+        let status = sqlx::query!(
+            r#"
+                SELECT
+                    (
+                        SELECT COUNT(DISTINCT message_id)
+                        FROM detections
+                        WHERE guild_id = $1
+                            AND author_id = $2
+                            AND detected_at > NOW() - MAKE_INTERVAL(days => $3::INTEGER)
+                    ) AS "detections!",
+                    EXISTS(
+                        SELECT 1
+                        FROM escalations
+                        WHERE rule_id = $4
+                            AND user_id = $2
+                            AND escalated_at > NOW() - MAKE_INTERVAL(days => $3::INTEGER)
+                    ) AS "escalated!";
+            "#,
+            guild_id.get() as i64,
+            user_id.get() as i64,
+            rule.window_days as i32,
+            rule.id,
+        )
+        .fetch_one(&data.db)
+        .await
+        .inspect_err(
+            |e| tracing::error!(err = ?e, rule_id = rule.id, "an error occurred when counting detections"),
+        )?;
+
+        if status.detections < rule.threshold as i64 || status.escalated {
+            continue;
+        }
+
+        let (detail, result) = apply(ctx, guild_id, user_id, &rule).await;
+
+        tracing::info!(guild_id = %guild_id, user = %user_id, rule_id = rule.id, detail = %detail, success = result.is_ok(), "escalated on repeat offender");
+
+        sqlx::query!(
+            r#"
+                INSERT INTO
+                    escalations (
+                        guild_id,
+                        rule_id,
+                        user_id,
+                        action,
+                        detail,
+                        detections,
+                        succeeded
+                    )
+                VALUES
+                    ($1, $2, $3, $4, $5, $6, $7);
+            "#,
+            guild_id.get() as i64,
+            rule.id,
+            user_id.get() as i64,
+            rule.action as EscalationAction,
+            detail,
+            status.detections as i16,
+            result.is_ok(),
+        )
+        .execute(&data.db)
+        .await
+        .inspect_err(
+            |e| tracing::error!(err = ?e, rule_id = rule.id, "an error occurred when recording escalation"),
+        )?;
+
+        // failing to post the escalation shouldn't keep the remaining rules from being applied.
+        if let Some(log_channel) = log_channel {
+            let _ = log_channel
+                .say(
+                    &ctx.http,
+                    format!(
+                        "{} reached {} detections in {} days and was {detail}{}.",
+                        user_id.mention(),
+                        status.detections,
+                        rule.window_days,
+                        if result.is_ok() { "" } else { " (failed)" },
+                    ),
+                )
+                .await
+                .inspect_err(
+                    |e| tracing::error!(err = ?e, "an error occurred when sending log message"),
+                );
+        }
+    }
+
+    Ok(())
+}
+
+/// Applies a single rule to a member, returning what was done along with whether it succeeded.
+async fn apply(
+    ctx: &serenity::Context,
+    guild_id: GuildId,
+    user_id: UserId,
+    rule: &EscalationRule,
+) -> (String, anyhow::Result<()>) {
+    match (rule.action, rule.timeout_minutes, rule.role_id) {
+        (EscalationAction::Timeout, Some(minutes), _) => {
+            let result = async {
+                let until = Utc::now() + TimeDelta::minutes(minutes as i64);
+
+                guild_id
+                    .edit_member(
+                        &ctx.http,
+                        user_id,
+                        EditMember::new()
+                            .disable_communication_until_datetime(Timestamp::from_unix_timestamp(
+                                until.timestamp(),
+                            )?)
+                            .audit_log_reason(AUDIT_LOG_REASON),
+                    )
+                    .await
+                    .inspect_err(
+                        |e| tracing::error!(err = ?e, "an error occurred when timing member out"),
+                    )?;
+
+                Ok(())
+            }
+            .await;
+
+            (format!("timed out for {minutes} minutes"), result)
+        }
+        (EscalationAction::Role, _, Some(role_id)) => {
+            let role_id = RoleId::new(role_id as u64);
+
+            let result = ctx
+                .http
+                .add_member_role(guild_id, user_id, role_id, Some(AUDIT_LOG_REASON))
+                .await
+                .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when assigning role"))
+                .map_err(anyhow::Error::from);
+
+            (format!("given {}", role_id.mention()), result)
+        }
+        (action, ..) => (
+            format!("{action:?}"),
+            Err(anyhow::anyhow!("escalation rule {} is incomplete", rule.id)),
+        ),
+    }
+}
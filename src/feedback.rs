@@ -0,0 +1,250 @@
+use image::{ImageBuffer, Rgb};
+use poise::serenity_prelude::{
+    self as serenity, ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateInteractionResponse, CreateInteractionResponseMessage, GuildId, MessageId,
+};
+use sqlx::{Pool, Postgres};
+
+use crate::detection::ImageMatch;
+use crate::hashing::{DHasher, ImageHasher, hamming_distance, preprocess};
+use crate::matching::hash_block_variants;
+use crate::models::database::FeedbackVerdict;
+use crate::permissions;
+use crate::{Data, Error};
+
+/// Prefix of the custom IDs of feedback buttons, as in `feedback:<verdict>:<message>:<artwork>:<hash>`.
+const FEEDBACK_PREFIX: &str = "feedback";
+/// Maximum distance between an image and an allowlisted fingerprint for it to be allowlisted.
+const ALLOWLIST_RADIUS: u32 = 4;
+/// Discord doesn't allow more action rows than this in a single message.
+const MAX_ACTION_ROWS: usize = 5;
+
+/// Hashes the first frame of an image, which is what's stored in the allowlist if it's reported as
+/// a false positive.
+pub fn fingerprint(data: &Data, frame: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<u8> {
+    DHasher.hash(&preprocess(frame, data.border_trim))
+}
+
+/// Whether an image was reported as a false positive in a guild, or everywhere.
+pub async fn is_allowlisted(
+    db: &Pool<Postgres>,
+    guild_id: Option<GuildId>,
+    fingerprint: &[u8],
+) -> anyhow::Result<bool> {
+    let Ok(fingerprint) = <[u8; 8]>::try_from(fingerprint) else {
+        return Ok(false);
+    };
+
+    let [b0, b1, b2, b3] = hash_block_variants(&fingerprint, ALLOWLIST_RADIUS);
+
+    let hashes = sqlx::query_scalar!(
+        r#"
+            SELECT hash
+            FROM match_allowlist
+            WHERE algorithm = $1 AND (guild_id IS NULL OR guild_id = $2) AND (
+                block_0 = ANY($3)
+                OR block_1 = ANY($4)
+                OR block_2 = ANY($5)
+                OR block_3 = ANY($6)
+            );
+        "#,
+        DHasher.id(),
+        guild_id.map(|id| id.get() as i64),
+        &b0,
+        &b1,
+        &b2,
+        &b3,
+    )
+    .fetch_all(db)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when fetching allowlist"))?;
+
+    Ok(hashes
+        .iter()
+        .any(|hash| hamming_distance(hash, &fingerprint) <= ALLOWLIST_RADIUS))
+}
+
+/// Buttons moderators can report each match of a message with.
+pub fn feedback_buttons(message_id: MessageId, matches: &[ImageMatch]) -> Vec<CreateActionRow> {
+    matches
+        .iter()
+        .take(MAX_ACTION_ROWS)
+        .enumerate()
+        .map(|(idx, image_match)| {
+            let suffix = if matches.len() > 1 {
+                format!(" #{}", idx + 1)
+            } else {
+                String::new()
+            };
+            let hash: String = image_match
+                .fingerprint
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect();
+            let custom_id = |verdict: &str| {
+                format!(
+                    "{FEEDBACK_PREFIX}:{verdict}:{message_id}:{}:{hash}",
+                    image_match.artwork_match.artwork_id
+                )
+            };
+
+            CreateActionRow::Buttons(vec![
+                CreateButton::new(custom_id("confirm"))
+                    .label(format!("confirm{suffix}"))
+                    .style(ButtonStyle::Success),
+                CreateButton::new(custom_id("false_positive"))
+                    .label(format!("false positive{suffix}"))
+                    .style(ButtonStyle::Danger),
+                CreateButton::new(custom_id("false_positive_global"))
+                    .label(format!("false positive everywhere{suffix}"))
+                    .style(ButtonStyle::Secondary),
+            ])
+        })
+        .collect()
+}
+
+/// Whether a pressed button is a feedback button, to be handled by [`handle_feedback`].
+pub fn is_feedback_button(press: &ComponentInteraction) -> bool {
+    press
+        .data
+        .custom_id
+        .starts_with(&format!("{FEEDBACK_PREFIX}:"))
+}
+
+/// Stores the feedback of a moderator pressing a feedback button, allowlisting the image if it's
+/// reported as a false positive. Only curators and members who can manage messages can report
+/// detections, and only curators can allowlist images everywhere.
+pub async fn handle_feedback(
+    ctx: &serenity::Context,
+    framework: poise::FrameworkContext<'_, Data, Error>,
+    data: &Data,
+    press: &ComponentInteraction,
+) -> anyhow::Result<()> {
+    let parts: Vec<&str> = press.data.custom_id.split(':').collect();
+
+    let [_, verdict, message_id, artwork_id, hash] = parts[..] else {
+        anyhow::bail!("malformed feedback button {}", press.data.custom_id);
+    };
+
+    let (verdict, global) = match verdict {
+        "confirm" => (FeedbackVerdict::Confirmed, false),
+        "false_positive" => (FeedbackVerdict::FalsePositive, false),
+        "false_positive_global" => (FeedbackVerdict::FalsePositive, true),
+        other => anyhow::bail!("unknown feedback verdict {other}"),
+    };
+    let message_id: i64 = message_id.parse()?;
+    let artwork_id: i64 = artwork_id.parse()?;
+    let hash = (0..hash.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hash.get(i..i + 2).unwrap_or_default(), 16))
+        .collect::<Result<Vec<u8>, _>>()?;
+
+    let is_owner = framework.options.owners.contains(&press.user.id);
+    let is_curator = is_owner || permissions::is_curator(&data.db, press.user.id).await?;
+    let is_moderator = press
+        .member
+        .as_ref()
+        .and_then(|member| member.permissions)
+        .is_some_and(|permissions| permissions.manage_messages());
+
+    let denied = if global && !is_curator {
+        Some("only curators can allowlist images everywhere.")
+    } else if !is_curator && !is_moderator {
+        Some("only moderators can report detections.")
+    } else {
+        None
+    };
+
+    if let Some(denied) = denied {
+        return respond(ctx, press, denied).await;
+    }
+
+    let guild_id = press.guild_id.map(|id| id.get() as i64);
+    let moderator_id = press.user.id.get() as i64;
+
+    let mut tx = data.db.begin().await?;
+
+    sqlx::query!(
+        r#"
+            INSERT INTO
+                detection_feedback (
+                    guild_id,
+                    message_id,
+                    artwork_id,
+                    image_hash,
+                    verdict,
+                    moderator_id
+                )
+            VALUES
+                ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (message_id, artwork_id, moderator_id) DO UPDATE SET
+                verdict = excluded.verdict,
+                created_at = NOW();
+        "#,
+        guild_id,
+        message_id,
+        artwork_id,
+        &hash,
+        verdict as FeedbackVerdict,
+        moderator_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when storing feedback"))?;
+
+    if verdict == FeedbackVerdict::FalsePositive {
+        sqlx::query!(
+            r#"
+                INSERT INTO
+                    match_allowlist (guild_id, algorithm, hash, artwork_id, added_by)
+                VALUES
+                    ($1, $2, $3, $4, $5)
+                ON CONFLICT (guild_id, algorithm, hash) DO NOTHING;
+            "#,
+            if global { None } else { guild_id },
+            DHasher.id(),
+            &hash,
+            artwork_id,
+            moderator_id,
+        )
+        .execute(&mut *tx)
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when allowlisting image"))?;
+    }
+
+    tx.commit().await?;
+
+    tracing::info!(message_id, artwork_id, moderator = %press.user.id, verdict = ?verdict, global, "stored detection feedback");
+
+    let content = match (verdict, global) {
+        (FeedbackVerdict::Confirmed, _) => "confirmed the detection, thanks!",
+        (FeedbackVerdict::FalsePositive, false) => {
+            "reported the detection as a false positive, this image won't match again in this server."
+        }
+        (FeedbackVerdict::FalsePositive, true) => {
+            "reported the detection as a false positive, this image won't match again anywhere."
+        }
+    };
+
+    respond(ctx, press, content).await
+}
+
+async fn respond(
+    ctx: &serenity::Context,
+    press: &ComponentInteraction,
+    content: &str,
+) -> anyhow::Result<()> {
+    press
+        .create_response(
+            ctx,
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+
+    Ok(())
+}
@@ -1,11 +1,383 @@
-use image::imageops::{FilterType, grayscale, resize};
+use std::borrow::Cow;
+use std::fmt;
+
+use image::imageops::{
+    FilterType, crop_imm, flip_horizontal, flip_vertical, grayscale, resize, rotate90, rotate180,
+    rotate270,
+};
 use image::{ImageBuffer, Luma, Rgb};
 
 const IMG_SCALE: u32 = 8;
+const PHASH_SCALE: u32 = 32;
+const WHASH_SCALE: u32 = 64;
+
+/// Grids, as `(columns, rows)`, every scraped artwork is split into for tile hashing. Tiles are
+/// numbered in order across all grids, so changing this invalidates every stored tile.
+pub const TILE_GRIDS: [(u32, u32); 4] = [(2, 1), (1, 2), (2, 2), (3, 3)];
+/// Grids an incoming image is split into when looking for tiles, including the whole image for when
+/// it's cut out of a larger artwork.
+pub const TILE_PROBE_GRIDS: [(u32, u32); 5] = [(1, 1), (2, 1), (1, 2), (2, 2), (3, 3)];
+/// Tiles whose grayscale signature spans fewer shades than this are skipped, since flat areas like
+/// blank backgrounds hash the same everywhere.
+const TILE_MIN_CONTRAST: u8 = 16;
+/// Images are never trimmed down to less than this many pixels in either dimension.
+const MIN_TRIMMED_SIZE: u32 = 16;
+/// Maximum per-channel difference from the border's color for a pixel to be part of it. Trimmed
+/// hashes aren't versioned by it, so changing it means rehashing every artwork.
+const BORDER_TRIM_TOLERANCE: u8 = 16;
+/// Version of the tile hashes computed by [`compute_tile_hashes`], bumped on any change to how they
+/// are computed, including changes to [`TILE_GRIDS`].
+pub const TILE_HASH_VERSION: i16 = 1;
+
+/// A perceptual hashing algorithm, whose hashes are stored for every artwork and compared by
+/// Hamming distance.
+pub trait ImageHasher: Send + Sync {
+    /// The identifier stored alongside every hash computed by this algorithm.
+    fn id(&self) -> &'static str;
+
+    /// The length of every hash computed by this algorithm, in bytes.
+    fn hash_len(&self) -> usize;
+
+    /// The version stored alongside every hash computed by this algorithm. It has to be bumped on
+    /// any change that alters the hashes computed, including to the resizing or grayscale
+    /// conversion the algorithm relies on, as hashes are only compared with hashes of the same
+    /// version.
+    fn version(&self) -> i16;
+
+    fn hash(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<u8>;
+}
+
+pub struct DHasher;
+pub struct PHasher;
+pub struct AHasher;
+pub struct WHasher;
+
+impl ImageHasher for DHasher {
+    fn id(&self) -> &'static str {
+        "dhash"
+    }
+
+    fn hash_len(&self) -> usize {
+        8
+    }
+
+    fn version(&self) -> i16 {
+        1
+    }
+
+    fn hash(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<u8> {
+        compute_dhash(img).to_vec()
+    }
+}
+
+impl ImageHasher for PHasher {
+    fn id(&self) -> &'static str {
+        "phash"
+    }
+
+    fn hash_len(&self) -> usize {
+        8
+    }
+
+    fn version(&self) -> i16 {
+        1
+    }
+
+    fn hash(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<u8> {
+        compute_phash(img).to_vec()
+    }
+}
+
+impl ImageHasher for AHasher {
+    fn id(&self) -> &'static str {
+        "ahash"
+    }
+
+    fn hash_len(&self) -> usize {
+        8
+    }
+
+    fn version(&self) -> i16 {
+        1
+    }
+
+    fn hash(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<u8> {
+        compute_ahash(img).to_vec()
+    }
+}
+
+impl ImageHasher for WHasher {
+    fn id(&self) -> &'static str {
+        "whash"
+    }
+
+    fn hash_len(&self) -> usize {
+        8
+    }
+
+    fn version(&self) -> i16 {
+        1
+    }
+
+    fn hash(&self, img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<u8> {
+        compute_whash(img).to_vec()
+    }
+}
+
+/// Every hashing algorithm computed for scraped artworks and incoming images.
+pub static HASHERS: &[&dyn ImageHasher] = &[&DHasher, &PHasher, &AHasher, &WHasher];
+
+pub fn get_hasher(id: &str) -> Option<&'static dyn ImageHasher> {
+    HASHERS.iter().find(|hasher| hasher.id() == id).copied()
+}
+
+/// Identifies which tree of the hash index a hash belongs in: hashes are only ever compared with
+/// hashes of the same key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HashKey {
+    pub algorithm: &'static str,
+    pub version: i16,
+    /// Whether borders were trimmed off the image before hashing.
+    pub trimmed: bool,
+}
+
+/// Trimming of uniform borders, such as letterboxing or padding to a square, off images before
+/// hashing them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BorderTrim;
+
+impl BorderTrim {
+    /// Reads `BORDER_TRIM`, returning `None` if trimming is disabled.
+    pub fn try_from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("BORDER_TRIM").as_deref() {
+            Err(_) | Ok("true") => Ok(Some(Self)),
+            Ok("false") => Ok(None),
+            Ok(other) => {
+                tracing::error!(value = %other, "an invalid BORDER_TRIM was specified");
+                anyhow::bail!("invalid BORDER_TRIM \"{other}\", expected \"true\" or \"false\"")
+            }
+        }
+    }
+
+    /// Crops every uniform border off an image. Images that are uniform throughout, or would be
+    /// left too small, are returned as is.
+    pub fn apply<'a>(
+        &self,
+        img: &'a ImageBuffer<Rgb<u8>, Vec<u8>>,
+    ) -> Cow<'a, ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        let (width, height) = img.dimensions();
+
+        if width == 0 || height == 0 {
+            return Cow::Borrowed(img);
+        }
+
+        let is_border = |x: u32, y: u32, reference: &Rgb<u8>| {
+            img.get_pixel(x, y)
+                .0
+                .iter()
+                .zip(reference.0)
+                .all(|(a, b)| a.abs_diff(b) <= BORDER_TRIM_TOLERANCE)
+        };
+
+        let reference = *img.get_pixel(0, 0);
+        let mut top = 0;
+        while top < height && (0..width).all(|x| is_border(x, top, &reference)) {
+            top += 1;
+        }
+
+        if top == height {
+            return Cow::Borrowed(img);
+        }
+
+        let reference = *img.get_pixel(0, height - 1);
+        let mut bottom = height;
+        while bottom > top && (0..width).all(|x| is_border(x, bottom - 1, &reference)) {
+            bottom -= 1;
+        }
+
+        let reference = *img.get_pixel(0, top);
+        let mut left = 0;
+        while left < width && (top..bottom).all(|y| is_border(left, y, &reference)) {
+            left += 1;
+        }
+
+        let reference = *img.get_pixel(width - 1, top);
+        let mut right = width;
+        while right > left && (top..bottom).all(|y| is_border(right - 1, y, &reference)) {
+            right -= 1;
+        }
+
+        let (trimmed_width, trimmed_height) = (right - left, bottom - top);
+
+        if (trimmed_width, trimmed_height) == (width, height)
+            || trimmed_width < MIN_TRIMMED_SIZE
+            || trimmed_height < MIN_TRIMMED_SIZE
+        {
+            return Cow::Borrowed(img);
+        }
+
+        Cow::Owned(crop_imm(img, left, top, trimmed_width, trimmed_height).to_image())
+    }
+}
+
+/// Applies every enabled preprocessing step to an image before hashing it.
+pub fn preprocess(
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    border_trim: Option<BorderTrim>,
+) -> Cow<'_, ImageBuffer<Rgb<u8>, Vec<u8>>> {
+    match border_trim {
+        Some(border_trim) => border_trim.apply(img),
+        None => Cow::Borrowed(img),
+    }
+}
+
+/// A transform applied to an incoming image before hashing, so mirrored or rotated reposts are
+/// still matched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transform {
+    Original,
+    FlipHorizontal,
+    FlipVertical,
+    Rotate90,
+    Rotate180,
+    Rotate270,
+}
+
+impl Transform {
+    pub const ALL: [Self; 6] = [
+        Self::Original,
+        Self::FlipHorizontal,
+        Self::FlipVertical,
+        Self::Rotate90,
+        Self::Rotate180,
+        Self::Rotate270,
+    ];
+
+    pub fn apply<'a>(
+        &self,
+        img: &'a ImageBuffer<Rgb<u8>, Vec<u8>>,
+    ) -> Cow<'a, ImageBuffer<Rgb<u8>, Vec<u8>>> {
+        match self {
+            Self::Original => Cow::Borrowed(img),
+            Self::FlipHorizontal => Cow::Owned(flip_horizontal(img)),
+            Self::FlipVertical => Cow::Owned(flip_vertical(img)),
+            Self::Rotate90 => Cow::Owned(rotate90(img)),
+            Self::Rotate180 => Cow::Owned(rotate180(img)),
+            Self::Rotate270 => Cow::Owned(rotate270(img)),
+        }
+    }
+}
+
+impl fmt::Display for Transform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Original => write!(f, "original"),
+            Self::FlipHorizontal => write!(f, "flipped horizontally"),
+            Self::FlipVertical => write!(f, "flipped vertically"),
+            Self::Rotate90 => write!(f, "rotated 90°"),
+            Self::Rotate180 => write!(f, "rotated 180°"),
+            Self::Rotate270 => write!(f, "rotated 270°"),
+        }
+    }
+}
+
+/// Every hash computed for a single image, keyed by algorithm.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageHashes {
+    /// Whether the image was preprocessed with border trimming before being hashed.
+    pub trimmed: bool,
+    /// Every hash along with the algorithm and version that computed it.
+    hashes: Vec<(&'static str, i16, Vec<u8>)>,
+}
+
+impl ImageHashes {
+    /// Hashes an image with every registered algorithm. `trimmed` records whether the image went
+    /// through border trimming in [`preprocess`].
+    pub fn compute(img: &ImageBuffer<Rgb<u8>, Vec<u8>>, trimmed: bool) -> Self {
+        Self {
+            trimmed,
+            hashes: HASHERS
+                .iter()
+                .map(|hasher| (hasher.id(), hasher.version(), hasher.hash(img)))
+                .collect(),
+        }
+    }
+
+    pub fn get(&self, algorithm: &str) -> Option<(HashKey, &[u8])> {
+        self.iter().find(|(key, _)| key.algorithm == algorithm)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (HashKey, &[u8])> {
+        self.hashes.iter().map(|(algorithm, version, hash)| {
+            (
+                HashKey {
+                    algorithm,
+                    version: *version,
+                    trimmed: self.trimmed,
+                },
+                hash.as_slice(),
+            )
+        })
+    }
+}
 
 pub fn compute_dhash(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> [u8; 8] {
     let signature = to_grayscale_signature(img);
 
+    dhash_from_signature(&signature)
+}
+
+/// Splits an image into the tiles of every grid in `grids`, returning the dHash of every tile along
+/// with its index. Flat tiles are skipped.
+pub fn compute_tile_hashes(
+    img: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    grids: &[(u32, u32)],
+) -> Vec<(i16, [u8; 8])> {
+    let mut hashes = vec![];
+    let mut tile_index = 0;
+
+    for (columns, rows) in grids {
+        let tile_width = img.width() / columns;
+        let tile_height = img.height() / rows;
+
+        for row in 0..*rows {
+            for column in 0..*columns {
+                let index = tile_index;
+                tile_index += 1;
+
+                if tile_width < IMG_SCALE + 1 || tile_height < IMG_SCALE {
+                    continue;
+                }
+
+                let tile = crop_imm(
+                    img,
+                    column * tile_width,
+                    row * tile_height,
+                    tile_width,
+                    tile_height,
+                )
+                .to_image();
+
+                let signature = to_grayscale_signature(&tile);
+                let (min, max) = signature
+                    .pixels()
+                    .fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                        (min.min(pixel[0]), max.max(pixel[0]))
+                    });
+
+                if max - min < TILE_MIN_CONTRAST {
+                    continue;
+                }
+
+                hashes.push((index, dhash_from_signature(&signature)));
+            }
+        }
+    }
+
+    hashes
+}
+
+fn dhash_from_signature(signature: &ImageBuffer<Luma<u8>, Vec<u8>>) -> [u8; 8] {
     let mut hash = 0u64;
     let mut bit_position = 0;
 
@@ -25,6 +397,112 @@ pub fn compute_dhash(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> [u8; 8] {
     hash.to_be_bytes()
 }
 
+/// Computes a DCT-based perceptual hash, which holds up better than dHash against re-compression,
+/// color grading and gamma shifts.
+pub fn compute_phash(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> [u8; 8] {
+    let gray_image = resize(
+        &grayscale(img),
+        PHASH_SCALE,
+        PHASH_SCALE,
+        FilterType::Triangle,
+    );
+
+    let cosines: Vec<Vec<f32>> = (0..IMG_SCALE)
+        .map(|u| {
+            (0..PHASH_SCALE)
+                .map(|x| {
+                    ((2 * x + 1) as f32 * u as f32 * std::f32::consts::PI
+                        / (2 * PHASH_SCALE) as f32)
+                        .cos()
+                })
+                .collect()
+        })
+        .collect();
+
+    // only the lowest 8x8 frequencies are kept, so the 2D DCT is done separably on those alone:
+    // first along every row, then along the columns of the result.
+    let rows: Vec<Vec<f32>> = (0..PHASH_SCALE)
+        .map(|y| {
+            cosines
+                .iter()
+                .map(|cosine| {
+                    (0..PHASH_SCALE)
+                        .map(|x| gray_image.get_pixel(x, y)[0] as f32 * cosine[x as usize])
+                        .sum()
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut coefficients = Vec::with_capacity((IMG_SCALE * IMG_SCALE) as usize);
+
+    for cosine in &cosines {
+        for u in 0..IMG_SCALE as usize {
+            coefficients.push(
+                rows.iter()
+                    .zip(cosine)
+                    .map(|(row, c)| row[u] * c)
+                    .sum::<f32>(),
+            );
+        }
+    }
+
+    let median = median(&coefficients);
+
+    pack_bits(coefficients.iter().map(|coefficient| *coefficient > median))
+}
+
+/// Computes an average hash, setting a bit for every pixel of the 8x8 grayscale image brighter than
+/// its mean.
+pub fn compute_ahash(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> [u8; 8] {
+    let gray_image = resize(&grayscale(img), IMG_SCALE, IMG_SCALE, FilterType::Triangle);
+
+    let pixels: Vec<f32> = gray_image.pixels().map(|pixel| pixel[0] as f32).collect();
+    let mean = pixels.iter().sum::<f32>() / pixels.len() as f32;
+
+    pack_bits(pixels.iter().map(|pixel| *pixel > mean))
+}
+
+/// Computes a wavelet hash, setting a bit for every coefficient of the 8x8 Haar approximation band
+/// above its median.
+pub fn compute_whash(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> [u8; 8] {
+    let gray_image = resize(
+        &grayscale(img),
+        WHASH_SCALE,
+        WHASH_SCALE,
+        FilterType::Triangle,
+    );
+
+    let mut size = WHASH_SCALE as usize;
+    let mut band: Vec<f32> = gray_image
+        .pixels()
+        .map(|pixel| pixel[0] as f32 / 255.0)
+        .collect();
+
+    // every level of the 2D Haar transform halves the approximation band, until it is 8x8.
+    while size > IMG_SCALE as usize {
+        let half = size / 2;
+        let mut next = Vec::with_capacity(half * half);
+
+        for y in 0..half {
+            for x in 0..half {
+                let top = band[2 * y * size + 2 * x] + band[2 * y * size + 2 * x + 1];
+                let bottom =
+                    band[(2 * y + 1) * size + 2 * x] + band[(2 * y + 1) * size + 2 * x + 1];
+
+                next.push((top + bottom) / 2.0);
+            }
+        }
+
+        band = next;
+        size = half;
+    }
+
+    let median = median(&band);
+
+    pack_bits(band.iter().map(|coefficient| *coefficient > median))
+}
+
 fn to_grayscale_signature(img: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> ImageBuffer<Luma<u8>, Vec<u8>> {
     let gray_image = grayscale(img);
 
@@ -37,3 +515,22 @@ pub fn hamming_distance(left: &[u8], right: &[u8]) -> u32 {
         .map(|(a, b)| (a ^ b).count_ones())
         .sum()
 }
+
+fn median(values: &[f32]) -> f32 {
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable_by(f32::total_cmp);
+
+    (sorted[sorted.len() / 2 - 1] + sorted[sorted.len() / 2]) / 2.0
+}
+
+fn pack_bits(bits: impl Iterator<Item = bool>) -> [u8; 8] {
+    let mut hash = 0u64;
+
+    for (bit_position, bit) in bits.enumerate() {
+        if bit {
+            hash |= 1 << bit_position;
+        }
+    }
+
+    hash.to_be_bytes()
+}
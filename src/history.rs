@@ -0,0 +1,60 @@
+use sqlx::{Pool, Postgres};
+
+use crate::actions::{Detection, ResponseAction};
+
+/// Stores every match of a message in the detection history, along with the action taken on it.
+pub async fn record_detection(
+    db: &Pool<Postgres>,
+    detection: &Detection<'_>,
+    action: &dyn ResponseAction,
+    action_succeeded: bool,
+) -> anyhow::Result<()> {
+    let message = detection.message;
+
+    let mut image_hashes: Vec<Vec<u8>> = vec![];
+    let mut artwork_ids: Vec<i64> = vec![];
+    let mut distances: Vec<i16> = vec![];
+    let mut confidences: Vec<f32> = vec![];
+
+    for image_match in detection.matches {
+        image_hashes.push(image_match.fingerprint.clone());
+        artwork_ids.push(image_match.artwork_match.artwork_id);
+        distances.push(image_match.artwork_match.hash_distance as i16);
+        confidences.push(image_match.artwork_match.confidence);
+    }
+
+    sqlx::query!(
+        r#"
+            INSERT INTO
+                detections (
+                    guild_id,
+                    channel_id,
+                    message_id,
+                    author_id,
+                    action,
+                    action_succeeded,
+                    image_hash,
+                    artwork_id,
+                    distance,
+                    confidence
+                )
+            SELECT $1, $2, $3, $4, $5, $6, *
+            FROM UNNEST($7::BYTEA[], $8::BIGINT[], $9::SMALLINT[], $10::REAL[]);
+        "#,
+        message.guild_id.map(|id| id.get() as i64),
+        message.channel_id.get() as i64,
+        message.id.get() as i64,
+        message.author.id.get() as i64,
+        action.id(),
+        action_succeeded,
+        &image_hashes,
+        &artwork_ids,
+        &distances,
+        &confidences,
+    )
+    .execute(db)
+    .await
+    .inspect_err(|e| tracing::error!(err = ?e, message_id = %message.id, "an error occurred when recording detection"))?;
+
+    Ok(())
+}
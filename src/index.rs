@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, PoisonError, RwLock};
+
+use futures::TryStreamExt;
+use sqlx::{Pool, Postgres};
+
+use crate::hashing::{HashKey, TILE_HASH_VERSION, get_hasher};
+
+/// An artwork entry stored in the hash index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedArtwork {
+    pub artwork_id: i64,
+    pub artist_id: i64,
+}
+
+/// An artwork tile entry stored in the hash index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IndexedTile {
+    pub artwork: IndexedArtwork,
+    pub tile_index: i16,
+}
+
+/// A BK-tree over 64-bit hashes, using the Hamming distance as its metric.
+///
+/// Every node holds the items sharing its exact hash, and its children are keyed by their distance
+/// to the node, so a radius query only has to descend into children whose key lies within
+/// `distance - radius..=distance + radius` (triangle inequality).
+#[derive(Debug)]
+pub struct BkTree<T> {
+    root: Option<BkNode<T>>,
+    len: usize,
+}
+
+#[derive(Debug)]
+struct BkNode<T> {
+    hash: u64,
+    items: Vec<T>,
+    children: BTreeMap<u32, BkNode<T>>,
+}
+
+impl<T> Default for BkTree<T> {
+    fn default() -> Self {
+        Self { root: None, len: 0 }
+    }
+}
+
+impl<T> BkTree<T> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn insert(&mut self, hash: u64, item: T) {
+        self.len += 1;
+
+        let Some(mut node) = self.root.as_mut() else {
+            self.root = Some(BkNode {
+                hash,
+                items: vec![item],
+                children: BTreeMap::new(),
+            });
+
+            return;
+        };
+
+        loop {
+            let distance = (node.hash ^ hash).count_ones();
+
+            if distance == 0 {
+                node.items.push(item);
+                return;
+            }
+
+            node = node.children.entry(distance).or_insert_with(|| BkNode {
+                hash,
+                items: vec![],
+                children: BTreeMap::new(),
+            });
+        }
+    }
+
+    /// Returns every item whose hash is at most `radius` bits away from `hash`, along with its
+    /// distance.
+    pub fn find(&self, hash: u64, radius: u32) -> Vec<(u32, &T)> {
+        let mut results = vec![];
+        let mut stack: Vec<&BkNode<T>> = self.root.iter().collect();
+
+        while let Some(node) = stack.pop() {
+            let distance = (node.hash ^ hash).count_ones();
+
+            if distance <= radius {
+                results.extend(node.items.iter().map(|item| (distance, item)));
+            }
+
+            stack.extend(
+                node.children
+                    .range(distance.saturating_sub(radius)..=distance + radius)
+                    .map(|(_, child)| child),
+            );
+        }
+
+        results
+    }
+
+    /// Removes every item not matching `predicate`. Emptied nodes are kept around to route lookups
+    /// to their children.
+    pub fn retain(&mut self, mut predicate: impl FnMut(&T) -> bool) {
+        let mut stack: Vec<&mut BkNode<T>> = self.root.iter_mut().collect();
+
+        while let Some(node) = stack.pop() {
+            let before = node.items.len();
+            node.items.retain(&mut predicate);
+            self.len -= before - node.items.len();
+
+            stack.extend(node.children.values_mut());
+        }
+    }
+}
+
+/// In-memory index of every known artwork's hashes, with one tree per hashing algorithm, shared
+/// across the bot.
+#[derive(Debug, Clone, Default)]
+pub struct HashIndex {
+    trees: Arc<RwLock<HashMap<HashKey, BkTree<IndexedArtwork>>>>,
+    /// Tile trees, keyed by whether borders were trimmed before hashing.
+    tiles: Arc<RwLock<HashMap<bool, BkTree<IndexedTile>>>>,
+}
+
+impl HashIndex {
+    /// Rebuilds the index from every artwork stored in the database.
+    pub async fn reload(&self, db: &Pool<Postgres>) -> anyhow::Result<()> {
+        let mut trees: HashMap<HashKey, BkTree<IndexedArtwork>> = HashMap::new();
+        let mut outdated = 0;
+
+        let mut rows = sqlx::query!(
+            r#"
+                SELECT aw.id, aw.artist_id AS "artist_id!", h.algorithm, h.version, h.hash, h.trimmed
+                FROM artwork_hashes h
+                JOIN artist_artworks aw ON h.artwork_id = aw.id
+                WHERE aw.artist_id IS NOT NULL;
+            "#,
+        )
+        .fetch(db);
+
+        while let Some(row) = rows.try_next().await.inspect_err(
+            |e| tracing::error!(err = ?e, "an error occurred when fetching artworks for hash index"),
+        )? {
+            let Some(hasher) = get_hasher(&row.algorithm) else {
+                continue;
+            };
+
+            if row.version != hasher.version() {
+                outdated += 1;
+                continue;
+            }
+
+            let Some(hash) = (row.hash.len() == hasher.hash_len())
+                .then(|| hash_to_u64(&row.hash))
+                .flatten()
+            else {
+                tracing::warn!(artwork_id = row.id, algorithm = %row.algorithm, "skipping artwork with malformed hash");
+                continue;
+            };
+
+            let key = HashKey {
+                algorithm: hasher.id(),
+                version: row.version,
+                trimmed: row.trimmed,
+            };
+
+            trees.entry(key).or_default().insert(
+                hash,
+                IndexedArtwork {
+                    artwork_id: row.id,
+                    artist_id: row.artist_id,
+                },
+            );
+        }
+
+        if outdated > 0 {
+            tracing::warn!(
+                hashes = outdated,
+                "skipped hashes computed by outdated algorithm versions, run `jane-doe rehash` to update them"
+            );
+        }
+
+        for (key, tree) in &trees {
+            tracing::info!(algorithm = %key.algorithm, version = key.version, trimmed = key.trimmed, hashes = tree.len(), "finished building hash index");
+        }
+
+        *self.trees.write().unwrap_or_else(PoisonError::into_inner) = trees;
+
+        Ok(())
+    }
+
+    /// Rebuilds the tile index from every artwork tile stored in the database.
+    pub async fn reload_tiles(&self, db: &Pool<Postgres>) -> anyhow::Result<()> {
+        let mut trees: HashMap<bool, BkTree<IndexedTile>> = HashMap::new();
+
+        let mut rows = sqlx::query!(
+            r#"
+                SELECT aw.id, aw.artist_id AS "artist_id!", t.tile_index, t.hash, t.trimmed
+                FROM artwork_tiles t
+                JOIN artist_artworks aw ON t.artwork_id = aw.id
+                WHERE t.version = $1 AND aw.artist_id IS NOT NULL;
+            "#,
+            TILE_HASH_VERSION,
+        )
+        .fetch(db);
+
+        while let Some(row) = rows.try_next().await.inspect_err(
+            |e| tracing::error!(err = ?e, "an error occurred when fetching artwork tiles for hash index"),
+        )? {
+            let Some(hash) = hash_to_u64(&row.hash) else {
+                continue;
+            };
+
+            trees.entry(row.trimmed).or_default().insert(
+                hash,
+                IndexedTile {
+                    artwork: IndexedArtwork {
+                        artwork_id: row.id,
+                        artist_id: row.artist_id,
+                    },
+                    tile_index: row.tile_index,
+                },
+            );
+        }
+
+        for (trimmed, tree) in &trees {
+            tracing::info!(
+                trimmed = trimmed,
+                tiles = tree.len(),
+                "finished building tile index"
+            );
+        }
+
+        *self.tiles.write().unwrap_or_else(PoisonError::into_inner) = trees;
+
+        Ok(())
+    }
+
+    pub fn insert(&self, key: HashKey, hash: &[u8], artwork: IndexedArtwork) {
+        let Some(hash) = hash_to_u64(hash) else {
+            return;
+        };
+
+        self.trees
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(key)
+            .or_default()
+            .insert(hash, artwork);
+    }
+
+    /// Returns every artwork at most `radius` bits away from `hash`, closest first.
+    pub fn find(&self, key: HashKey, hash: &[u8], radius: u32) -> Vec<(u32, IndexedArtwork)> {
+        let Some(hash) = hash_to_u64(hash) else {
+            return vec![];
+        };
+
+        let trees = self.trees.read().unwrap_or_else(PoisonError::into_inner);
+
+        let Some(tree) = trees.get(&key) else {
+            return vec![];
+        };
+
+        let mut results: Vec<_> = tree
+            .find(hash, radius)
+            .into_iter()
+            .map(|(distance, artwork)| (distance, *artwork))
+            .collect();
+
+        results.sort_by_key(|(distance, artwork)| (*distance, artwork.artwork_id));
+
+        results
+    }
+
+    pub fn insert_tile(&self, trimmed: bool, hash: &[u8], tile: IndexedTile) {
+        let Some(hash) = hash_to_u64(hash) else {
+            return;
+        };
+
+        self.tiles
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(trimmed)
+            .or_default()
+            .insert(hash, tile);
+    }
+
+    /// Returns every artwork tile at most `radius` bits away from `hash`.
+    pub fn find_tiles(&self, trimmed: bool, hash: &[u8], radius: u32) -> Vec<(u32, IndexedTile)> {
+        let Some(hash) = hash_to_u64(hash) else {
+            return vec![];
+        };
+
+        let trees = self.tiles.read().unwrap_or_else(PoisonError::into_inner);
+
+        let Some(tree) = trees.get(&trimmed) else {
+            return vec![];
+        };
+
+        tree.find(hash, radius)
+            .into_iter()
+            .map(|(distance, tile)| (distance, *tile))
+            .collect()
+    }
+
+    pub fn remove_artist(&self, artist_id: i64) {
+        for tree in self
+            .trees
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values_mut()
+        {
+            tree.retain(|artwork| artwork.artist_id != artist_id);
+        }
+
+        for tree in self
+            .tiles
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .values_mut()
+        {
+            tree.retain(|tile| tile.artwork.artist_id != artist_id);
+        }
+    }
+}
+
+/// The index only supports 64-bit hashes, longer or shorter ones are skipped.
+fn hash_to_u64(hash: &[u8]) -> Option<u64> {
+    hash.try_into().ok().map(u64::from_be_bytes)
+}
@@ -10,7 +10,10 @@ use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::util::SubscriberInitExt;
 use tracing_subscriber::{EnvFilter, Registry};
 
-use crate::{Data, commands, event_handler, scraper};
+use crate::{Data, Error, commands, event_handler, scraper};
+
+/// Number of recent messages cached per channel.
+const MAX_CACHED_MESSAGES: usize = 100;
 
 pub async fn init_database() -> anyhow::Result<Pool<Postgres>> {
     let db_url = std::env::var("DATABASE_URL").expect("missing DATABASE_URL");
@@ -31,6 +34,65 @@ pub async fn init_database() -> anyhow::Result<Pool<Postgres>> {
     Ok(db)
 }
 
+/// Replies to errors in commands and denied permissions ephemerally, so that they're only visible
+/// to whoever invoked the slash command, leaving every other error to poise.
+async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
+    let (ctx, content) = match error {
+        poise::FrameworkError::Command { error, ctx, .. } => {
+            tracing::error!(err = ?error, command = %ctx.command().qualified_name, "an error occurred when running command");
+
+            (
+                ctx,
+                "an error occurred when running this command.".to_string(),
+            )
+        }
+        poise::FrameworkError::ArgumentParse {
+            error, input, ctx, ..
+        } => {
+            let content = match input {
+                Some(input) => format!("cannot parse `{input}` as argument: {error}."),
+                None => format!("{error}."),
+            };
+
+            (ctx, content)
+        }
+        poise::FrameworkError::CommandCheckFailed {
+            error: None, ctx, ..
+        }
+        | poise::FrameworkError::NotAnOwner { ctx, .. } => {
+            let _ = ctx
+                .send(
+                    poise::CreateReply::default()
+                        .content("you don't have permission to run this command.")
+                        .ephemeral(true),
+                )
+                .await
+                .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"));
+
+            return;
+        }
+        error => {
+            if let Err(e) = poise::builtins::on_error(error).await {
+                tracing::error!(err = ?e, "an error occurred when handling error");
+            }
+
+            return;
+        }
+    };
+
+    let _ = ctx
+        .send(
+            poise::CreateReply::default()
+                .content(format!(
+                    "{content}\ntype `j!help {0}` or `/help {0}` for usage.",
+                    ctx.command().qualified_name
+                ))
+                .ephemeral(true),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"));
+}
+
 pub async fn init_discord_client(token: &str, data: Data) -> anyhow::Result<serenity::Client> {
     let intents =
         serenity::GatewayIntents::non_privileged() | serenity::GatewayIntents::MESSAGE_CONTENT;
@@ -41,7 +103,13 @@ pub async fn init_discord_client(token: &str, data: Data) -> anyhow::Result<sere
                 commands::status::status(),
                 commands::alias::alias(),
                 commands::artist::artist(),
+                commands::check::check(),
+                commands::check::check_message(),
+                commands::config::config(),
+                commands::curator::curator(),
+                commands::escalation::escalation(),
                 commands::help::help(),
+                commands::history::history(),
             ],
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: Some("j!".into()),
@@ -50,6 +118,7 @@ pub async fn init_discord_client(token: &str, data: Data) -> anyhow::Result<sere
             event_handler: |ctx, event, framework, data| {
                 Box::pin(event_handler(ctx, event, framework, data))
             },
+            on_error: |error| Box::pin(on_error(error)),
             ..Default::default()
         })
         .setup(|ctx, _ready, framework| {
@@ -68,7 +137,13 @@ pub async fn init_discord_client(token: &str, data: Data) -> anyhow::Result<sere
         })
         .build();
 
+    // recent messages are cached so their updates, such as links getting unfurled, can be applied
+    // to them instead of fetching every updated message again.
+    let mut cache_settings = serenity::cache::Settings::default();
+    cache_settings.max_messages = MAX_CACHED_MESSAGES;
+
     let client = serenity::ClientBuilder::new(token, intents)
+        .cache_settings(cache_settings)
         .framework(framework)
         .await?;
 
@@ -80,7 +155,8 @@ pub fn init_telemetry() -> anyhow::Result<()> {
 
     Registry::default()
         .with(env_filter)
-        .with(tracing_subscriber::fmt::layer())
+        // logs go to stderr, leaving stdout to the output of commands like `check`.
+        .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .try_init()?;
 
     Ok(())
@@ -127,6 +203,10 @@ pub fn spawn_background_task(data: &Data) {
                 .await;
 
                 let _ = scraper::scrape_task(&data_clone).await;
+                let _ = data_clone
+                    .calibration
+                    .reload(&data_clone.db, &data_clone.hash_selection)
+                    .await;
             }
         }
         .in_current_span(),
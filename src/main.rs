@@ -2,30 +2,63 @@ use std::process::ExitCode;
 
 use clap::Parser;
 use poise::serenity_prelude as serenity;
-use rayon::prelude::*;
 use sqlx::{Pool, Postgres};
 
+use crate::actions::{Detection, get_action};
+use crate::audit::check_task;
 use crate::booru::BooruClient;
+use crate::calibration::{CalibrationCache, calibrate_task};
 use crate::cli::{Cli, Command};
 use crate::constants::STARTUP_TIME;
-use crate::hashing::{compute_dhash, hamming_distance};
+use crate::detection::{
+    CheckedImages, ImageMatch, MessageImage, close_artists_note, match_note,
+    sticker_checking_from_env, support_note,
+};
+use crate::feedback::feedback_buttons;
+use crate::hashing::BorderTrim;
+use crate::index::HashIndex;
 use crate::init::spawn_background_task;
-use crate::models::database::ArtworkMatch;
-use crate::scraper::scrape_task;
+use crate::matching::{HashSelection, MatchBackend, TileMatching};
+use crate::media::VideoDecoding;
+use crate::scraper::{rehash_task, scrape_task};
+use crate::settings::{GuildSettings, SettingsCache};
 
+mod actions;
+mod audit;
 mod booru;
+mod calibration;
 mod cli;
 mod commands;
 mod constants;
+mod detection;
+mod escalation;
+mod feedback;
 mod hashing;
+mod history;
+mod index;
 mod init;
+mod matching;
+mod media;
 mod models;
+mod modlog;
+mod permissions;
 mod scraper;
+mod settings;
 
 #[derive(Debug, Clone)]
 struct Data {
     db: Pool<Postgres>,
     booru: BooruClient,
+    index: HashIndex,
+    match_backend: MatchBackend,
+    hash_selection: HashSelection,
+    tile_matching: Option<TileMatching>,
+    border_trim: Option<BorderTrim>,
+    video_decoding: Option<VideoDecoding>,
+    sticker_checking: bool,
+    checked_images: CheckedImages,
+    settings: SettingsCache,
+    calibration: CalibrationCache,
 }
 
 type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -35,108 +68,213 @@ type Context<'a> = poise::Context<'a, Data, Error>;
 async fn event_handler(
     ctx: &serenity::Context,
     event: &serenity::FullEvent,
-    _framework: poise::FrameworkContext<'_, Data, Error>,
+    framework: poise::FrameworkContext<'_, Data, Error>,
     data: &Data,
 ) -> Result<(), Error> {
-    if let serenity::FullEvent::Message { new_message } = event {
-        if new_message.author.bot || new_message.attachments.is_empty() {
-            return Ok(());
+    match event {
+        serenity::FullEvent::Message { new_message } => {
+            if new_message.author.bot {
+                return Ok(());
+            }
+
+            check_message(ctx, data, new_message).await?;
         }
+        // edits, as well as links getting unfurled into embeds after the message is sent, arrive
+        // as updates of the message, which is then checked for its new images.
+        serenity::FullEvent::MessageUpdate { new, event, .. } => {
+            if event.author.as_ref().is_some_and(|author| author.bot) {
+                return Ok(());
+            }
+
+            if event.content.is_none() && event.attachments.is_none() && event.embeds.is_none() {
+                return Ok(());
+            }
+
+            // updates are far more common than messages worth checking, so guilds that wouldn't
+            // check the message anyway are ruled out before fetching it.
+            let settings = match event.guild_id {
+                Some(guild_id) => data.settings.get(&data.db, guild_id).await?,
+                None => GuildSettings::default(),
+            };
+
+            if !settings.enabled || settings.excluded_channels.contains(&event.channel_id) {
+                return Ok(());
+            }
+
+            let message = match new {
+                Some(message) => message.clone(),
+                None => event.channel_id.message(ctx, event.id).await.inspect_err(
+                    |e| tracing::error!(err = ?e, "an error occurred when fetching message"),
+                )?,
+            };
 
-        let mut matches: Vec<ArtworkMatch> = vec![];
-
-        for attachment in &new_message.attachments {
-            if attachment.dimensions().is_some() {
-                let bytes = attachment.download().await?;
-                let image = image::load_from_memory(&bytes)?.to_rgb8();
-
-                let dhash = compute_dhash(&image);
-
-                let exact_matches = sqlx::query!(
-                    r#"
-                        SELECT aa.name as artist_name
-                        FROM artist_artworks aw
-                        JOIN ai_artists aa
-                        ON aw.artist_id = aa.id
-                        WHERE aw.dhash = $1;
-                    "#,
-                    &dhash,
-                )
-                .fetch_all(&data.db)
-                .await
-                .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when fetching exact matches from database"))?;
-
-                if let Some(artwork_match) = exact_matches.first() {
-                    tracing::info!(attachment_id = %attachment.id, user = %new_message.author.id, "got known AI art");
-
-                    matches.push(ArtworkMatch {
-                        artist_name: artwork_match.artist_name.clone(),
-                        confidence: 100.0,
-                        hash_distance: 0,
-                    });
-
-                    continue;
-                }
-
-                let artworks = sqlx::query!(
-                    r#"
-                        SELECT aa.name as artist_name, aw.dhash
-                        FROM artist_artworks aw
-                        JOIN ai_artists aa ON aw.artist_id = aa.id
-                    "#,
-                )
-                .fetch_all(&data.db)
-                .await
-                .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when fetching known artworks from database"))?;
-
-                if let Some(artwork_match) = artworks.into_par_iter().find_map_any(|artwork| {
-                    let distance = hamming_distance(&artwork.dhash, &dhash);
-
-                    if distance < 15 {
-                        Some(ArtworkMatch {
-                            artist_name: artwork.artist_name,
-                            confidence: (64 - distance) as f32 / 64.0 * 100.0,
-                            hash_distance: distance,
-                        })
-                    } else {
-                        None
-                    }
-                }) {
-                    matches.push(artwork_match);
-
-                    continue;
-                };
+            if message.author.bot {
+                return Ok(());
             }
+
+            check_message(ctx, data, &message).await?;
+        }
+        serenity::FullEvent::InteractionCreate {
+            interaction: serenity::Interaction::Component(press),
+        } if feedback::is_feedback_button(press) => {
+            feedback::handle_feedback(ctx, framework, data, press).await?;
         }
+        _ => {}
+    }
+
+    Ok(())
+}
 
-        if matches.is_empty() {
+/// Checks every image of a message that wasn't checked yet, taking the guild's response action on
+/// it if any of them is known AI art.
+async fn check_message(
+    ctx: &serenity::Context,
+    data: &Data,
+    message: &serenity::Message,
+) -> Result<(), Error> {
+    let settings = match message.guild_id {
+        Some(guild_id) => data.settings.get(&data.db, guild_id).await?,
+        None => GuildSettings::default(),
+    };
+
+    if !settings.enabled || settings.excluded_channels.contains(&message.channel_id) {
+        return Ok(());
+    }
+
+    if let Some(guild_id) = message.guild_id
+        && !settings.excluded_roles.is_empty()
+    {
+        let roles = match &message.member {
+            Some(member) => member.roles.clone(),
+            None => {
+                guild_id
+                    .member(ctx, message.author.id)
+                    .await
+                    .inspect_err(
+                        |e| tracing::error!(err = ?e, "an error occurred when fetching member"),
+                    )?
+                    .roles
+            }
+        };
+
+        if roles
+            .iter()
+            .any(|role| settings.excluded_roles.contains(role))
+        {
             return Ok(());
         }
+    }
+
+    let images = MessageImage::collect(&ctx.http, data, message).await;
+    let images = data.checked_images.retain_unchecked(message.id, images);
+
+    let mut matches: Vec<ImageMatch> = vec![];
+
+    for image in &images {
+        let image_match = match image.check(data, settings.match_radius).await {
+            Ok(Some(image_match)) => image_match,
+            Ok(None) => continue,
+            // failures are logged when checking, and shouldn't keep the other images from being
+            // checked, or the image from being checked again when the message gets updated.
+            Err(_) => {
+                data.checked_images.forget(message.id, image);
+                continue;
+            }
+        };
+
+        let artwork_match = &image_match.artwork_match;
+
+        // failing to read the allowlist shouldn't keep known AI art from being reported.
+        if feedback::is_allowlisted(&data.db, message.guild_id, &image_match.fingerprint)
+            .await
+            .unwrap_or(false)
+        {
+            tracing::info!(image = %image.name(), message_id = %message.id, artwork_id = artwork_match.artwork_id, "skipped allowlisted image");
+            continue;
+        }
+
+        tracing::info!(image = %image.name(), message_id = %message.id, user = %message.author.id, artwork_id = artwork_match.artwork_id, distance = artwork_match.hash_distance, transform = %artwork_match.transform, "got known AI art");
+
+        matches.push(image_match);
+    }
+
+    if matches.is_empty() {
+        return Ok(());
+    }
+
+    let mut reply_str = String::from("This message contains known AI art ");
 
-        let mut reply_str = String::from("This message contains known AI art ");
+    if matches.len() == 1 {
+        let artwork_match = &matches[0].artwork_match;
 
-        if matches.len() == 1 {
+        reply_str += &format!(
+            "by {}, {}{}{}.{}",
+            artwork_match.artist_name,
+            artwork_match.level.as_match(),
+            match_note(artwork_match),
+            support_note(artwork_match),
+            close_artists_note(artwork_match),
+        );
+    } else {
+        reply_str += "by:\n";
+
+        for (idx, ImageMatch { artwork_match, .. }) in matches.iter().enumerate() {
             reply_str += &format!(
-                "by {}, confidence is {:.2}%.",
-                matches[0].artist_name, matches[0].confidence
+                "{}. {}, {}{}{}.{}\n",
+                idx + 1,
+                artwork_match.artist_name,
+                artwork_match.level.as_match(),
+                match_note(artwork_match),
+                support_note(artwork_match),
+                close_artists_note(artwork_match),
             );
-        } else {
-            reply_str += "by:\n";
-
-            for (idx, artwork_match) in matches.iter().enumerate() {
-                reply_str += &format!(
-                    "{}. {}, confidence is {:.2}%.\n",
-                    idx + 1,
-                    artwork_match.artist_name,
-                    artwork_match.confidence
-                );
-            }
         }
+    }
 
-        new_message
-            .reply_ping(&ctx.http, reply_str)
-            .await
-            .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending reply"))?;
+    // settings only ever hold the identifiers of known actions.
+    let action = get_action(settings.response_action)
+        .ok_or_else(|| anyhow::anyhow!("unknown response action {}", settings.response_action))?;
+
+    let detection = Detection {
+        message,
+        matches: &matches,
+        summary: &reply_str,
+        components: feedback_buttons(message.id, &matches),
+    };
+
+    let result = action.respond(ctx, &detection).await;
+
+    tracing::info!(message_id = %message.id, user = %message.author.id, action = action.id(), success = result.is_ok(), "took action on known AI art");
+
+    // failing to record the detection shouldn't keep it from being logged.
+    let recorded = history::record_detection(&data.db, &detection, action, result.is_ok())
+        .await
+        .is_ok();
+
+    // failing to log the detection shouldn't keep the member from being escalated on.
+    if let Some(log_channel) = settings.log_channel {
+        let _ =
+            modlog::log_detection(ctx, data, log_channel, &detection, action, result.is_ok()).await;
+    }
+
+    // repeat offenders are counted by their recorded detections.
+    if recorded && let Some(guild_id) = message.guild_id {
+        escalation::escalate(ctx, data, guild_id, message.author.id, settings.log_channel).await?;
+    }
+
+    result?;
+
+    Ok(())
+}
+
+/// Loads every known artwork into memory, if artworks are matched in memory.
+async fn load_index(data: &Data) -> anyhow::Result<()> {
+    if data.match_backend == MatchBackend::Memory {
+        data.index.reload(&data.db).await?;
+
+        if data.tile_matching.is_some() {
+            data.index.reload_tiles(&data.db).await?;
+        }
     }
 
     Ok(())
@@ -166,7 +304,29 @@ async fn inner_main() -> anyhow::Result<()> {
     let db = init::init_database().await?;
     let booru = BooruClient::new()?;
 
-    let data = Data { db, booru };
+    let index = HashIndex::default();
+    let match_backend = MatchBackend::try_from_env()?;
+    let hash_selection = HashSelection::try_from_env()?;
+    let tile_matching = TileMatching::try_from_env()?;
+    let border_trim = BorderTrim::try_from_env()?;
+    let video_decoding = VideoDecoding::try_from_env()?;
+    let sticker_checking = sticker_checking_from_env()?;
+    let calibration = CalibrationCache::load(&db, &hash_selection).await?;
+
+    let data = Data {
+        db,
+        booru,
+        index,
+        match_backend,
+        hash_selection,
+        tile_matching,
+        border_trim,
+        video_decoding,
+        sticker_checking,
+        checked_images: CheckedImages::default(),
+        settings: SettingsCache::default(),
+        calibration,
+    };
 
     tracing::info!("finished initializing!");
 
@@ -174,6 +334,8 @@ async fn inner_main() -> anyhow::Result<()> {
         Some(Command::Start) | None => {
             let token = std::env::var("DISCORD_TOKEN").expect("missing DISCORD_TOKEN");
 
+            load_index(&data).await?;
+
             let mut client = init::init_discord_client(&token, data.clone()).await?;
             spawn_background_task(&data);
 
@@ -182,6 +344,20 @@ async fn inner_main() -> anyhow::Result<()> {
         Some(Command::Scrape) => {
             scrape_task(&data).await?;
         }
+        Some(Command::Rehash) => {
+            rehash_task(&data).await?;
+        }
+        Some(Command::Calibrate) => {
+            calibrate_task(&data).await?;
+        }
+        Some(Command::Check {
+            paths,
+            radius,
+            json,
+        }) => {
+            load_index(&data).await?;
+            check_task(&data, &paths, radius, json).await?;
+        }
     }
 
     Ok(())
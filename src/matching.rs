@@ -0,0 +1,647 @@
+use std::borrow::Cow;
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+use image::{ImageBuffer, Rgb};
+use sqlx::{Pool, Postgres};
+
+use crate::Data;
+use crate::calibration::ConfidenceLevel;
+use crate::hashing::{
+    HASHERS, HashKey, ImageHashes, TILE_GRIDS, TILE_HASH_VERSION, TILE_PROBE_GRIDS, Transform,
+    compute_tile_hashes, get_hasher, hamming_distance, preprocess,
+};
+use crate::index::{IndexedArtwork, IndexedTile};
+use crate::models::database::{ArtistMatch, ArtworkMatch, ArtworkSource, Platform};
+
+/// Maximum distance between an image and an artwork's hashes for them to be considered matching.
+pub const MATCH_RADIUS: u32 = 14;
+/// Number of 16-bit blocks each 64-bit hash is split into for multi-index hashing.
+const HASH_BLOCKS: u32 = 4;
+/// Maximum distance for a single tile to be considered matching.
+const TILE_RADIUS: u32 = 8;
+/// Number of closest artworks an image's match is ranked among, and grouped by artist.
+const TOP_K: usize = 10;
+
+/// Where known artworks are looked up when matching an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MatchBackend {
+    /// Look up the in-memory BK-tree, built at startup.
+    Memory,
+    /// Query the multi-index hash blocks stored in Postgres.
+    Database,
+}
+
+impl MatchBackend {
+    pub fn try_from_env() -> anyhow::Result<Self> {
+        match std::env::var("MATCH_BACKEND").as_deref() {
+            Err(_) | Ok("memory") => Ok(Self::Memory),
+            Ok("database") => Ok(Self::Database),
+            Ok(other) => {
+                tracing::error!(backend = %other, "an unknown MATCH_BACKEND was specified");
+                anyhow::bail!(
+                    "unknown MATCH_BACKEND \"{other}\", expected \"memory\" or \"database\""
+                )
+            }
+        }
+    }
+}
+
+/// Which hashing algorithms an image has to match against, and whether any or all of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HashSelection {
+    pub algorithms: Vec<&'static str>,
+    /// Whether every algorithm has to be close enough, reporting the farthest distance, instead of
+    /// any of them, reporting the closest.
+    pub require_all: bool,
+}
+
+impl HashSelection {
+    /// Parses `MATCH_HASHES`, a list of algorithms separated by either `,` (any of them has to
+    /// match) or `+` (all of them have to match).
+    pub fn try_from_env() -> anyhow::Result<Self> {
+        let selection = std::env::var("MATCH_HASHES").unwrap_or_else(|_| "dhash,phash".into());
+
+        let require_all = selection.contains('+');
+
+        if require_all && selection.contains(',') {
+            tracing::error!(hashes = %selection, "MATCH_HASHES mixes `,` and `+`");
+            anyhow::bail!("MATCH_HASHES can't mix `,` and `+`");
+        }
+
+        let algorithms = selection
+            .split([',', '+'])
+            .map(|id| {
+                get_hasher(id.trim()).map(|hasher| hasher.id()).ok_or_else(|| {
+                    tracing::error!(algorithm = %id, "an unknown hashing algorithm was specified in MATCH_HASHES");
+                    anyhow::anyhow!(
+                        "unknown hashing algorithm \"{id}\", expected one of: {}",
+                        HASHERS.iter().map(|hasher| hasher.id()).collect::<Vec<_>>().join(", ")
+                    )
+                })
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self {
+            algorithms,
+            require_all,
+        })
+    }
+
+    /// Identifies the selection along with the current version of every algorithm in it, as in
+    /// `dhash@1,phash@1`.
+    pub fn key(&self) -> String {
+        self.algorithms
+            .iter()
+            .filter_map(|id| get_hasher(id))
+            .map(|hasher| format!("{}@{}", hasher.id(), hasher.version()))
+            .collect::<Vec<_>>()
+            .join(if self.require_all { "+" } else { "," })
+    }
+
+    /// Combines the distances of every algorithm of the selection, as the closest one if any of them
+    /// has to match or the farthest one if all of them have to. Returns `None` if an algorithm
+    /// that has to match has no distance, or if none has any.
+    pub fn combine(&self, distances: impl Iterator<Item = Option<u32>>) -> Option<u32> {
+        if self.require_all {
+            distances.collect::<Option<Vec<_>>>()?.into_iter().max()
+        } else {
+            distances.flatten().min()
+        }
+    }
+}
+
+/// Crop-resistant matching, where an image matches an artwork if enough of its tiles do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileMatching {
+    /// The number of distinct artwork tiles that have to match.
+    pub threshold: usize,
+}
+
+impl TileMatching {
+    /// Reads `TILE_MATCHING` and `TILE_MATCH_THRESHOLD`, returning `None` if tile matching is
+    /// disabled.
+    pub fn try_from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("TILE_MATCHING").as_deref() {
+            Err(_) | Ok("false") => return Ok(None),
+            Ok("true") => {}
+            Ok(other) => {
+                tracing::error!(value = %other, "an invalid TILE_MATCHING was specified");
+                anyhow::bail!("invalid TILE_MATCHING \"{other}\", expected \"true\" or \"false\"")
+            }
+        }
+
+        let threshold = match std::env::var("TILE_MATCH_THRESHOLD") {
+            Ok(threshold) => threshold.parse().inspect_err(
+                |e| tracing::error!(err = ?e, "an invalid TILE_MATCH_THRESHOLD was specified"),
+            )?,
+            Err(_) => 3,
+        };
+
+        Ok(Some(Self { threshold }))
+    }
+}
+
+/// Matches every frame of an image against every known artwork, returning the closest match of any
+/// frame. Matches of the whole frame are preferred over matches by tiles.
+pub async fn match_frames(
+    data: &Data,
+    frames: &[ImageBuffer<Rgb<u8>, Vec<u8>>],
+    radius: u32,
+) -> anyhow::Result<Option<ArtworkMatch>> {
+    let mut best: Option<ArtworkMatch> = None;
+
+    for frame in frames {
+        let Some(artwork_match) = match_image(data, frame, radius).await? else {
+            continue;
+        };
+
+        let rank = |m: &ArtworkMatch| (m.matched_tiles.is_some(), m.hash_distance);
+
+        if best
+            .as_ref()
+            .is_none_or(|best| rank(&artwork_match) < rank(best))
+        {
+            best = Some(artwork_match);
+        }
+
+        if best.as_ref().is_some_and(|best| rank(best) == (false, 0)) {
+            break;
+        }
+    }
+
+    Ok(best)
+}
+
+/// Matches an image against every known artwork, also trying every [`Transform`] of it to catch
+/// mirrored or rotated reposts. Returns the closest match, if any is at most `radius` bits away,
+/// along with the artists of the [`TOP_K`] closest artworks.
+pub async fn match_image(
+    data: &Data,
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    radius: u32,
+) -> anyhow::Result<Option<ArtworkMatch>> {
+    let closest = closest_artworks(data, std::slice::from_ref(image), radius, TOP_K).await?;
+
+    if let Some(&(distance, transform, artwork)) = closest.first() {
+        // artist ID -> (closest distance, number of artworks), in order of the closest artworks.
+        let mut artists: Vec<(i64, u32, usize)> = vec![];
+
+        for (distance, _, artwork) in &closest {
+            match artists.iter_mut().find(|(id, ..)| *id == artwork.artist_id) {
+                Some(artist) => artist.2 += 1,
+                None => artists.push((artwork.artist_id, *distance, 1)),
+            }
+        }
+
+        let artist_ids: Vec<i64> = artists.iter().map(|(id, ..)| *id).collect();
+        let names = fetch_artist_names(&data.db, &artist_ids).await?;
+
+        let Some(artist_name) = names.get(&artwork.artist_id).cloned() else {
+            return Ok(None);
+        };
+
+        let calibration = data.calibration.get();
+
+        return Ok(Some(ArtworkMatch {
+            artwork_id: artwork.artwork_id,
+            artist_name,
+            confidence: calibration.confidence(distance),
+            false_match_rate: calibration.false_match_rate(distance),
+            level: calibration.level(distance),
+            hash_distance: distance,
+            transform,
+            matched_tiles: None,
+            artists: artists
+                .into_iter()
+                .filter_map(|(id, hash_distance, artworks)| {
+                    Some(ArtistMatch {
+                        artist_name: names.get(&id)?.clone(),
+                        hash_distance,
+                        artworks,
+                    })
+                })
+                .collect(),
+        }));
+    }
+
+    let Some(tile_matching) = data.tile_matching else {
+        return Ok(None);
+    };
+
+    match_tiles(data, image, tile_matching).await
+}
+
+/// Hashes an image the way artworks are hashed at scrape time. When borders are trimmed, the image
+/// is also hashed without trimming, to still match artworks scraped before trimming was enabled.
+fn hash_image(data: &Data, image: &ImageBuffer<Rgb<u8>, Vec<u8>>) -> Vec<ImageHashes> {
+    let preprocessed = preprocess(image, data.border_trim);
+    let hashes = ImageHashes::compute(&preprocessed, data.border_trim.is_some());
+
+    if !hashes.trimmed {
+        return vec![hashes];
+    }
+
+    let untrimmed = match preprocessed {
+        Cow::Borrowed(_) => {
+            let mut untrimmed = hashes.clone();
+            untrimmed.trimmed = false;
+            untrimmed
+        }
+        Cow::Owned(_) => ImageHashes::compute(image, false),
+    };
+
+    vec![hashes, untrimmed]
+}
+
+/// Matches the tiles of an image against the stored tiles of every known artwork, returning the
+/// artwork with the most matching tiles if there are at least as many as the threshold.
+async fn match_tiles(
+    data: &Data,
+    image: &ImageBuffer<Rgb<u8>, Vec<u8>>,
+    tile_matching: TileMatching,
+) -> anyhow::Result<Option<ArtworkMatch>> {
+    // artwork ID -> (artwork, matching tile indices, closest distance)
+    let mut candidates: HashMap<i64, (IndexedArtwork, HashSet<i16>, u32)> = HashMap::new();
+
+    let trimmed = data.border_trim.is_some();
+    let mut probes: Vec<_> =
+        compute_tile_hashes(&preprocess(image, data.border_trim), &TILE_PROBE_GRIDS)
+            .into_iter()
+            .map(|(_, hash)| (trimmed, hash))
+            .collect();
+
+    if trimmed {
+        probes.extend(
+            compute_tile_hashes(image, &TILE_PROBE_GRIDS)
+                .into_iter()
+                .map(|(_, hash)| (false, hash)),
+        );
+    }
+
+    for (trimmed, hash) in probes {
+        let tiles = match data.match_backend {
+            MatchBackend::Memory => data.index.find_tiles(trimmed, &hash, TILE_RADIUS),
+            MatchBackend::Database => {
+                find_tiles_in_database(&data.db, trimmed, &hash, TILE_RADIUS).await?
+            }
+        };
+
+        for (distance, tile) in tiles {
+            let entry = candidates
+                .entry(tile.artwork.artwork_id)
+                .or_insert_with(|| (tile.artwork, HashSet::new(), distance));
+
+            entry.1.insert(tile.tile_index);
+            entry.2 = entry.2.min(distance);
+        }
+    }
+
+    let Some((artwork, tiles, distance)) = candidates
+        .into_values()
+        .filter(|(_, tiles, _)| tiles.len() >= tile_matching.threshold)
+        .max_by_key(|(artwork, tiles, distance)| {
+            (tiles.len(), Reverse(*distance), Reverse(artwork.artwork_id))
+        })
+    else {
+        return Ok(None);
+    };
+
+    let Some(artist_name) = fetch_artist_name(&data.db, artwork.artist_id).await? else {
+        return Ok(None);
+    };
+
+    let tile_count: u32 = TILE_GRIDS
+        .iter()
+        .map(|(columns, rows)| columns * rows)
+        .sum();
+
+    Ok(Some(ArtworkMatch {
+        artwork_id: artwork.artwork_id,
+        artist_name: artist_name.clone(),
+        confidence: (tiles.len() as f32 / tile_count as f32 * 100.0).min(100.0),
+        false_match_rate: None,
+        // tiles only ever cover part of the image, so they can't vouch for more than this.
+        level: ConfidenceLevel::Possible,
+        hash_distance: distance,
+        transform: Transform::Original,
+        matched_tiles: Some(tiles.len()),
+        artists: vec![ArtistMatch {
+            artist_name,
+            hash_distance: distance,
+            artworks: 1,
+        }],
+    }))
+}
+
+async fn fetch_artist_name(db: &Pool<Postgres>, artist_id: i64) -> anyhow::Result<Option<String>> {
+    let artist_name = sqlx::query_scalar!(
+        r#"
+            SELECT name
+            FROM ai_artists
+            WHERE id = $1;
+        "#,
+        artist_id,
+    )
+    .fetch_optional(db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when fetching artist from database"),
+    )?;
+
+    Ok(artist_name)
+}
+
+/// Fetches the names of every given artist, keyed by artist ID.
+async fn fetch_artist_names(
+    db: &Pool<Postgres>,
+    artist_ids: &[i64],
+) -> anyhow::Result<HashMap<i64, String>> {
+    let artists = sqlx::query!(
+        r#"
+            SELECT id, name
+            FROM ai_artists
+            WHERE id = ANY($1);
+        "#,
+        artist_ids,
+    )
+    .fetch_all(db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when fetching artists from database"),
+    )?;
+
+    Ok(artists
+        .into_iter()
+        .map(|artist| (artist.id, artist.name))
+        .collect())
+}
+
+/// Fetches where every given artwork was scraped from, keyed by artwork ID.
+pub async fn fetch_artwork_sources(
+    db: &Pool<Postgres>,
+    artwork_ids: &[i64],
+) -> anyhow::Result<HashMap<i64, ArtworkSource>> {
+    let sources = sqlx::query_as!(
+        ArtworkSource,
+        r#"
+            SELECT
+                aw.id AS artwork_id,
+                a.name AS artist_name,
+                aw.platform AS "platform: Platform",
+                aw.original_id
+            FROM artist_artworks aw
+            JOIN ai_artists a ON aw.artist_id = a.id
+            WHERE aw.id = ANY($1);
+        "#,
+        artwork_ids,
+    )
+    .fetch_all(db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when fetching artworks from database"),
+    )?;
+
+    Ok(sources
+        .into_iter()
+        .map(|source| (source.artwork_id, source))
+        .collect())
+}
+
+/// Returns up to `limit` known artworks at most `radius` bits away from any frame of an image, or
+/// any [`Transform`] of it, along with the transform they're closest with, closest first.
+pub async fn closest_artworks(
+    data: &Data,
+    frames: &[ImageBuffer<Rgb<u8>, Vec<u8>>],
+    radius: u32,
+    limit: usize,
+) -> anyhow::Result<Vec<(u32, Transform, IndexedArtwork)>> {
+    let mut closest: HashMap<i64, (u32, Transform, IndexedArtwork)> = HashMap::new();
+
+    for frame in frames {
+        for transform in Transform::ALL {
+            for hashes in hash_image(data, &transform.apply(frame)) {
+                for (distance, artwork) in find_artworks(data, &hashes, radius).await? {
+                    closest
+                        .entry(artwork.artwork_id)
+                        .and_modify(|entry| {
+                            if distance < entry.0 {
+                                *entry = (distance, transform, artwork);
+                            }
+                        })
+                        .or_insert((distance, transform, artwork));
+                }
+            }
+        }
+    }
+
+    let mut closest: Vec<_> = closest.into_values().collect();
+    closest.sort_by_key(|(distance, _, artwork)| (*distance, artwork.artwork_id));
+    closest.truncate(limit);
+
+    Ok(closest)
+}
+
+/// Returns every known artwork at most `radius` bits away from `hashes`, closest first.
+pub async fn find_artworks(
+    data: &Data,
+    hashes: &ImageHashes,
+    radius: u32,
+) -> anyhow::Result<Vec<(u32, IndexedArtwork)>> {
+    let selection = &data.hash_selection;
+
+    // artwork ID -> (number of matching algorithms, closest distance, farthest distance, artwork)
+    let mut distances: HashMap<i64, (usize, u32, u32, IndexedArtwork)> = HashMap::new();
+    // animated artworks have hashes for several frames, only the closest one counts.
+    let mut closest_frames: HashMap<i64, (u32, IndexedArtwork)> = HashMap::new();
+
+    for algorithm in &selection.algorithms {
+        let Some((key, hash)) = hashes.get(algorithm) else {
+            continue;
+        };
+
+        let results = match data.match_backend {
+            MatchBackend::Memory => data.index.find(key, hash, radius),
+            MatchBackend::Database => {
+                find_artworks_in_database(&data.db, key, hash, radius).await?
+            }
+        };
+
+        closest_frames.clear();
+
+        for (distance, artwork) in results {
+            closest_frames
+                .entry(artwork.artwork_id)
+                .and_modify(|closest| closest.0 = closest.0.min(distance))
+                .or_insert((distance, artwork));
+        }
+
+        for (distance, artwork) in closest_frames.values().copied() {
+            let entry = distances
+                .entry(artwork.artwork_id)
+                .or_insert((0, distance, distance, artwork));
+
+            entry.0 += 1;
+            entry.1 = entry.1.min(distance);
+            entry.2 = entry.2.max(distance);
+        }
+    }
+
+    let mut results: Vec<_> = distances
+        .into_values()
+        .filter_map(|(count, closest, farthest, artwork)| {
+            if !selection.require_all {
+                Some((closest, artwork))
+            } else if count == selection.algorithms.len() {
+                Some((farthest, artwork))
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    results.sort_by_key(|(distance, artwork)| (*distance, artwork.artwork_id));
+
+    Ok(results)
+}
+
+async fn find_artworks_in_database(
+    db: &Pool<Postgres>,
+    key: HashKey,
+    hash: &[u8],
+    radius: u32,
+) -> anyhow::Result<Vec<(u32, IndexedArtwork)>> {
+    let Ok(hash) = <[u8; 8]>::try_from(hash) else {
+        return Ok(vec![]);
+    };
+
+    let [b0, b1, b2, b3] = hash_block_variants(&hash, radius);
+
+    let candidates = sqlx::query!(
+        r#"
+            SELECT aw.id, aw.artist_id AS "artist_id!", h.hash
+            FROM artwork_hashes h
+            JOIN artist_artworks aw ON h.artwork_id = aw.id
+            WHERE h.algorithm = $1 AND h.version = $2 AND h.trimmed = $3
+                AND aw.artist_id IS NOT NULL AND (
+                    h.block_0 = ANY($4)
+                    OR h.block_1 = ANY($5)
+                    OR h.block_2 = ANY($6)
+                    OR h.block_3 = ANY($7)
+                );
+        "#,
+        key.algorithm,
+        key.version,
+        key.trimmed,
+        &b0,
+        &b1,
+        &b2,
+        &b3,
+    )
+    .fetch_all(db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when fetching candidate artworks"),
+    )?;
+
+    let mut results: Vec<_> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let distance = hamming_distance(&candidate.hash, &hash);
+
+            (distance <= radius).then_some((
+                distance,
+                IndexedArtwork {
+                    artwork_id: candidate.id,
+                    artist_id: candidate.artist_id,
+                },
+            ))
+        })
+        .collect();
+
+    results.sort_by_key(|(distance, artwork)| (*distance, artwork.artwork_id));
+
+    Ok(results)
+}
+
+async fn find_tiles_in_database(
+    db: &Pool<Postgres>,
+    trimmed: bool,
+    hash: &[u8; 8],
+    radius: u32,
+) -> anyhow::Result<Vec<(u32, IndexedTile)>> {
+    let [b0, b1, b2, b3] = hash_block_variants(hash, radius);
+
+    let candidates = sqlx::query!(
+        r#"
+            SELECT aw.id, aw.artist_id AS "artist_id!", t.tile_index, t.hash
+            FROM artwork_tiles t
+            JOIN artist_artworks aw ON t.artwork_id = aw.id
+            WHERE t.version = $1 AND t.trimmed = $2 AND aw.artist_id IS NOT NULL AND (
+                t.block_0 = ANY($3)
+                OR t.block_1 = ANY($4)
+                OR t.block_2 = ANY($5)
+                OR t.block_3 = ANY($6)
+            );
+        "#,
+        TILE_HASH_VERSION,
+        trimmed,
+        &b0,
+        &b1,
+        &b2,
+        &b3,
+    )
+    .fetch_all(db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when fetching candidate artwork tiles"),
+    )?;
+
+    Ok(candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let distance = hamming_distance(&candidate.hash, hash);
+
+            (distance <= radius).then_some((
+                distance,
+                IndexedTile {
+                    artwork: IndexedArtwork {
+                        artwork_id: candidate.id,
+                        artist_id: candidate.artist_id,
+                    },
+                    tile_index: candidate.tile_index,
+                },
+            ))
+        })
+        .collect())
+}
+
+/// Returns, for every block of `hash`, every block value a hash within `radius` of it could share.
+pub fn hash_block_variants(hash: &[u8; 8], radius: u32) -> [Vec<i32>; HASH_BLOCKS as usize] {
+    // by the pigeonhole principle, a hash within `radius` of ours has at least one block within
+    // `radius / HASH_BLOCKS` of the matching block of ours.
+    let block_radius = radius / HASH_BLOCKS;
+
+    [0, 2, 4, 6].map(|i| block_variants(u16::from_be_bytes([hash[i], hash[i + 1]]), block_radius))
+}
+
+/// Returns every 16-bit value at most `radius` bits away from `block`.
+fn block_variants(block: u16, radius: u32) -> Vec<i32> {
+    let mut variants = vec![block];
+
+    for _ in 0..radius {
+        let mut next = variants.clone();
+
+        for variant in &variants {
+            for bit in 0..16 {
+                next.push(variant ^ (1 << bit));
+            }
+        }
+
+        next.sort_unstable();
+        next.dedup();
+        variants = next;
+    }
+
+    variants.into_iter().map(i32::from).collect()
+}
@@ -0,0 +1,216 @@
+use std::io::{Cursor, Write};
+use std::process::Stdio;
+use std::time::Duration;
+
+use image::codecs::gif::GifDecoder;
+use image::codecs::png::PngDecoder;
+use image::codecs::pnm::PnmDecoder;
+use image::codecs::webp::WebPDecoder;
+use image::{AnimationDecoder, DynamicImage, ImageBuffer, ImageFormat, Rgb};
+use tokio::process::Command;
+
+/// Maximum number of frames hashed per animation or video.
+pub const MAX_SAMPLED_FRAMES: usize = 8;
+/// Animations are only decoded up to this many frames, so huge ones can't stall the bot.
+const MAX_DECODED_FRAMES: usize = 1000;
+/// Videos are only decoded up to this many keyframes.
+const MAX_DECODED_KEYFRAMES: usize = 64;
+/// Extracted keyframes are scaled down to at most this height, which is plenty for hashing.
+const MAX_KEYFRAME_HEIGHT: u32 = 512;
+/// How long ffmpeg gets to extract the keyframes of a video.
+const FFMPEG_TIMEOUT: Duration = Duration::from_secs(30);
+/// Major brands of `ftyp` boxes that mark AVIF and HEIC stills rather than videos.
+const IMAGE_BRANDS: [&[u8]; 4] = [b"avif", b"heic", b"heix", b"mif1"];
+
+#[derive(Debug, Clone)]
+pub struct VideoDecoding {
+    pub ffmpeg: String,
+}
+
+impl VideoDecoding {
+    /// Reads `VIDEO_DECODING` and `FFMPEG_PATH`, returning `None` if video decoding is disabled,
+    /// which it is by default. Fails if it's enabled but ffmpeg can't be run.
+    pub fn try_from_env() -> anyhow::Result<Option<Self>> {
+        match std::env::var("VIDEO_DECODING").as_deref() {
+            Err(_) | Ok("false") => return Ok(None),
+            Ok("true") => {}
+            Ok(other) => {
+                tracing::error!(value = %other, "an invalid VIDEO_DECODING was specified");
+                anyhow::bail!("invalid VIDEO_DECODING \"{other}\", expected \"true\" or \"false\"")
+            }
+        }
+
+        let ffmpeg = std::env::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_string());
+
+        let status = std::process::Command::new(&ffmpeg)
+            .arg("-version")
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .inspect_err(|e| tracing::error!(err = ?e, ffmpeg = %ffmpeg, "an error occurred when running ffmpeg, set VIDEO_DECODING=false to disable video decoding"))?;
+
+        anyhow::ensure!(status.success(), "{ffmpeg} -version exited with {status}");
+
+        Ok(Some(Self { ffmpeg }))
+    }
+
+    /// Extracts the keyframes of a video with ffmpeg, returning an evenly spaced sample of up to
+    /// [`MAX_SAMPLED_FRAMES`] of them.
+    pub async fn extract_keyframes(
+        &self,
+        bytes: &[u8],
+    ) -> anyhow::Result<Vec<ImageBuffer<Rgb<u8>, Vec<u8>>>> {
+        // MP4 can't be demuxed from a pipe if its index is at the end, so the video goes to a file.
+        let mut file = tempfile::NamedTempFile::new()?;
+        file.write_all(bytes)?;
+
+        let scale = format!("scale=-2:'min(ih,{MAX_KEYFRAME_HEIGHT})'");
+        let max_frames = MAX_DECODED_KEYFRAMES.to_string();
+
+        let output = Command::new(&self.ffmpeg)
+            .args(["-v", "error", "-skip_frame", "nokey", "-i"])
+            .arg(file.path())
+            .args(["-an", "-vf", &scale, "-fps_mode", "passthrough"])
+            .args([
+                "-frames:v",
+                &max_frames,
+                "-f",
+                "image2pipe",
+                "-c:v",
+                "ppm",
+                "-",
+            ])
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .output();
+
+        let output = tokio::time::timeout(FFMPEG_TIMEOUT, output)
+            .await
+            .map_err(|_| anyhow::anyhow!("ffmpeg timed out after {FFMPEG_TIMEOUT:?}"))??;
+
+        if !output.status.success() {
+            anyhow::bail!(
+                "ffmpeg exited with {}: {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+
+        let mut frames = Cursor::new(output.stdout.as_slice());
+        let frames = std::iter::from_fn(|| {
+            if frames.position() >= output.stdout.len() as u64 {
+                return None;
+            }
+
+            // ffmpeg writes the frames back to back, each one with its own header.
+            Some(
+                PnmDecoder::new(&mut frames)
+                    .and_then(DynamicImage::from_decoder)
+                    .map(|frame| frame.to_rgb8())
+                    .map_err(anyhow::Error::from),
+            )
+        });
+
+        let frames = sample_frames(frames)?;
+        anyhow::ensure!(!frames.is_empty(), "ffmpeg extracted no keyframes");
+
+        Ok(frames)
+    }
+}
+
+/// Decodes every frame of an image or video that's worth hashing. Still images yield a single
+/// frame, while animated GIF, APNG and WebP images and MP4 and WebM videos yield an evenly spaced
+/// sample of up to [`MAX_SAMPLED_FRAMES`] keyframes.
+pub async fn decode_frames(
+    bytes: &[u8],
+    video: Option<&VideoDecoding>,
+) -> anyhow::Result<Vec<ImageBuffer<Rgb<u8>, Vec<u8>>>> {
+    let frames = match image::guess_format(bytes) {
+        Ok(ImageFormat::Gif) => Some(GifDecoder::new(Cursor::new(bytes))?.into_frames()),
+        Ok(ImageFormat::Png) => {
+            let decoder = PngDecoder::new(Cursor::new(bytes))?;
+
+            if decoder.is_apng()? {
+                Some(decoder.apng()?.into_frames())
+            } else {
+                None
+            }
+        }
+        Ok(ImageFormat::WebP) => {
+            let decoder = WebPDecoder::new(Cursor::new(bytes))?;
+
+            if decoder.has_animation() {
+                Some(decoder.into_frames())
+            } else {
+                None
+            }
+        }
+        Err(_) if is_video(bytes) => {
+            let Some(video) = video else {
+                anyhow::bail!("video decoding is disabled");
+            };
+
+            return video.extract_keyframes(bytes).await;
+        }
+        _ => None,
+    };
+
+    match frames {
+        Some(frames) => sample_frames(
+            frames
+                .take(MAX_DECODED_FRAMES)
+                .map(|frame| Ok(DynamicImage::ImageRgba8(frame?.into_buffer()).to_rgb8())),
+        ),
+        None => Ok(vec![image::load_from_memory(bytes)?.to_rgb8()]),
+    }
+}
+
+/// Whether the bytes look like an MP4 (or QuickTime) or WebM (or Matroska) video. AVIF and HEIC
+/// stills share the MP4 container, so they're told apart by their major brand.
+fn is_video(bytes: &[u8]) -> bool {
+    let mp4 = bytes.get(4..8) == Some(b"ftyp")
+        && bytes
+            .get(8..12)
+            .is_some_and(|brand| !IMAGE_BRANDS.contains(&brand));
+
+    mp4 || bytes.starts_with(&[0x1a, 0x45, 0xdf, 0xa3])
+}
+
+/// Keeps an evenly spaced sample of frames without holding the whole animation in memory, by
+/// halving the kept frames whenever there are too many of them.
+fn sample_frames(
+    frames: impl Iterator<Item = anyhow::Result<ImageBuffer<Rgb<u8>, Vec<u8>>>>,
+) -> anyhow::Result<Vec<ImageBuffer<Rgb<u8>, Vec<u8>>>> {
+    let mut kept = vec![];
+    let mut stride = 1;
+    let mut count = 0;
+
+    for (index, frame) in frames.enumerate() {
+        let frame = frame?;
+        count = index + 1;
+
+        if index % stride != 0 {
+            continue;
+        }
+
+        kept.push((index, frame));
+
+        if kept.len() > 2 * MAX_SAMPLED_FRAMES {
+            stride *= 2;
+            kept.retain(|(index, _)| index % stride == 0);
+        }
+    }
+
+    tracing::debug!(frames = count, kept = kept.len(), "sampled frames");
+
+    if kept.len() <= MAX_SAMPLED_FRAMES {
+        return Ok(kept.into_iter().map(|(_, frame)| frame).collect());
+    }
+
+    let step = kept.len() as f32 / MAX_SAMPLED_FRAMES as f32;
+
+    Ok((0..MAX_SAMPLED_FRAMES)
+        .map(|i| std::mem::take(&mut kept[(i as f32 * step) as usize].1))
+        .collect())
+}
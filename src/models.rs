@@ -1,4 +1,5 @@
 pub mod danbooru;
 pub mod database;
+pub mod discord;
 pub mod gelbooru;
 pub mod rule34;
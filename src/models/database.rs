@@ -1,26 +1,51 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 
+use crate::calibration::ConfidenceLevel;
+use crate::hashing::Transform;
+
 #[derive(Debug, Clone)]
 pub struct AiArtist {
-    pub id: i64,
     pub name: String,
     pub aliases: Vec<String>,
 }
 
 #[derive(Debug, Clone)]
-pub struct ArtistArtwork {
-    id: i64,
-    artist_id: i64,
-    original_url: String,
-    dhash: Vec<u8>,
-    scraped_at: chrono::NaiveDateTime,
+pub struct ArtworkMatch {
+    pub artwork_id: i64,
+    pub artist_name: String,
+    pub confidence: f32,
+    /// Estimated fraction of unrelated images matching this closely, if the confidence levels were
+    /// calibrated and this wasn't matched by its tiles.
+    pub false_match_rate: Option<f32>,
+    pub level: ConfidenceLevel,
+    pub hash_distance: u32,
+    /// The transform applied to the incoming image for it to match.
+    pub transform: Transform,
+    /// The number of artwork tiles matched, if this was matched by its tiles.
+    pub matched_tiles: Option<usize>,
+    /// Every artist among the closest artworks, closest first, the matched one included.
+    pub artists: Vec<ArtistMatch>,
 }
 
+/// An artist among the artworks closest to an image.
 #[derive(Debug, Clone)]
-pub struct ArtworkMatch {
+pub struct ArtistMatch {
     pub artist_name: String,
-    pub confidence: f32,
+    /// Distance of the artist's closest artwork.
     pub hash_distance: u32,
+    /// Number of the artist's artworks among the closest ones.
+    pub artworks: usize,
+}
+
+/// Where a known artwork was scraped from.
+#[derive(Debug, Clone)]
+pub struct ArtworkSource {
+    pub artwork_id: i64,
+    pub artist_name: String,
+    pub platform: Platform,
+    pub original_id: i64,
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd, Deserialize, Serialize, sqlx::Type)]
@@ -30,3 +55,72 @@ pub enum Platform {
     Danbooru,
     Rule34,
 }
+
+impl Platform {
+    /// The URL of a post on this platform.
+    pub fn post_url(&self, id: i64) -> String {
+        match self {
+            Self::Gelbooru => format!("https://gelbooru.com/index.php?page=post&s=view&id={id}"),
+            Self::Danbooru => format!("https://danbooru.donmai.us/posts/{id}"),
+            Self::Rule34 => format!("https://rule34.xxx/index.php?page=post&s=view&id={id}"),
+        }
+    }
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Gelbooru => write!(f, "gelbooru"),
+            Self::Danbooru => write!(f, "danbooru"),
+            Self::Rule34 => write!(f, "rule34"),
+        }
+    }
+}
+
+/// What a moderator reported a detection as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "feedback_verdict", rename_all = "snake_case")]
+pub enum FeedbackVerdict {
+    Confirmed,
+    FalsePositive,
+}
+
+/// What is done to a member who reaches an escalation rule's threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "escalation_action", rename_all = "snake_case")]
+pub enum EscalationAction {
+    /// Time the member out for the rule's duration.
+    Timeout,
+    /// Give the member the rule's role.
+    Role,
+}
+
+/// A guild's rule for escalating on members with repeated detections.
+#[derive(Debug, Clone)]
+pub struct EscalationRule {
+    pub id: i64,
+    /// Number of detections within the window that trigger the rule.
+    pub threshold: i16,
+    pub window_days: i16,
+    pub action: EscalationAction,
+    pub timeout_minutes: Option<i32>,
+    pub role_id: Option<i64>,
+}
+
+impl fmt::Display for EscalationRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "after {} detections in {} days, ",
+            self.threshold, self.window_days
+        )?;
+
+        match (self.action, self.timeout_minutes, self.role_id) {
+            (EscalationAction::Timeout, Some(minutes), _) => {
+                write!(f, "timeout for {minutes} minutes")
+            }
+            (EscalationAction::Role, _, Some(role_id)) => write!(f, "assign <@&{role_id}>"),
+            (action, ..) => write!(f, "{action:?}"),
+        }
+    }
+}
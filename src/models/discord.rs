@@ -0,0 +1,28 @@
+use poise::serenity_prelude::{Attachment, Embed, StickerItem};
+use serde::Deserialize;
+
+/// A message fetched from Discord's API, only with what serenity doesn't parse yet.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawMessage {
+    /// Copies of the messages forwarded by this message.
+    #[serde(default)]
+    pub message_snapshots: Vec<MessageSnapshot>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MessageSnapshot {
+    pub message: SnapshotMessage,
+}
+
+/// The subset of a forwarded message's fields that is copied along with it.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SnapshotMessage {
+    #[serde(default)]
+    pub content: String,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+    #[serde(default)]
+    pub embeds: Vec<Embed>,
+    #[serde(default)]
+    pub sticker_items: Vec<StickerItem>,
+}
@@ -0,0 +1,169 @@
+use poise::serenity_prelude::{
+    self as serenity, ChannelId, CreateAttachment, CreateEmbed, CreateEmbedAuthor,
+    CreateEmbedFooter, CreateMessage, Mentionable,
+};
+
+use crate::Data;
+use crate::actions::{Detection, ResponseAction};
+use crate::detection::{ImageMatch, false_match_note, match_note};
+use crate::matching;
+
+/// Discord doesn't allow more embeds than this in a single message.
+const MAX_EMBEDS: usize = 10;
+/// Matched images are uploaded along with the log message up to this many bytes in total, below
+/// the smallest upload limit of a guild.
+const MAX_EVIDENCE_SIZE: usize = 8 * 1024 * 1024;
+
+/// Copy of a matched image uploaded along with the log message, since the original goes away once
+/// the message is deleted.
+fn evidence(idx: usize, image_match: &ImageMatch) -> Option<CreateAttachment> {
+    // images are named by their format, and videos by the extension they were posted with.
+    let extension = match image::guess_format(&image_match.bytes) {
+        Ok(format) => format.extensions_str().first().copied()?,
+        Err(_) => {
+            image_match
+                .image
+                .name()
+                .split(['?', '#'])
+                .next()?
+                .rsplit_once('.')?
+                .1
+        }
+    };
+
+    Some(CreateAttachment::bytes(
+        image_match.bytes.clone(),
+        format!("evidence-{idx}.{}", extension.to_ascii_lowercase()),
+    ))
+}
+
+/// Sends an embed detailing every match of a message to a guild's moderator log channel, along with
+/// the action that was taken on it, copies of the matched images and buttons to report the matches
+/// with.
+pub async fn log_detection(
+    ctx: &serenity::Context,
+    data: &Data,
+    log_channel: ChannelId,
+    detection: &Detection<'_>,
+    action: &dyn ResponseAction,
+    action_succeeded: bool,
+) -> anyhow::Result<()> {
+    let message = detection.message;
+    let artwork_ids: Vec<i64> = detection
+        .matches
+        .iter()
+        .map(|image_match| image_match.artwork_match.artwork_id)
+        .collect();
+    let sources = matching::fetch_artwork_sources(&data.db, &artwork_ids).await?;
+
+    let action = if action_succeeded {
+        action.id().to_string()
+    } else {
+        format!("{} (failed)", action.id())
+    };
+
+    let mut uploaded = 0;
+    let evidence: Vec<Option<CreateAttachment>> = detection
+        .matches
+        .iter()
+        .take(MAX_EMBEDS)
+        .enumerate()
+        .map(|(idx, image_match)| {
+            if uploaded + image_match.bytes.len() > MAX_EVIDENCE_SIZE {
+                return None;
+            }
+
+            let attachment = evidence(idx, image_match)?;
+            uploaded += image_match.bytes.len();
+
+            Some(attachment)
+        })
+        .collect();
+
+    let embeds: Vec<CreateEmbed> = detection
+        .matches
+        .iter()
+        .zip(&evidence)
+        .map(|(image_match, attachment)| {
+            let artwork_match = &image_match.artwork_match;
+            let source = match sources.get(&artwork_match.artwork_id) {
+                Some(source) => format!(
+                    "[{} #{}]({})",
+                    source.platform,
+                    source.original_id,
+                    source.platform.post_url(source.original_id)
+                ),
+                None => "unknown".to_string(),
+            };
+
+            CreateEmbed::default()
+                .title("known AI art detected")
+                .url(message.link())
+                .author(
+                    CreateEmbedAuthor::new(message.author.tag()).icon_url(message.author.face()),
+                )
+                .description(format!(
+                    "posted by {} in {}\n[jump to message]({})",
+                    message.author.mention(),
+                    message.channel_id.mention(),
+                    message.link()
+                ))
+                .thumbnail(match attachment {
+                    Some(attachment) if image::guess_format(&image_match.bytes).is_ok() => {
+                        format!("attachment://{}", attachment.filename)
+                    }
+                    _ => image_match.image.url().to_string(),
+                })
+                .field("artist", &artwork_match.artist_name, true)
+                .field(
+                    "confidence",
+                    format!("{}{}", artwork_match.level, false_match_note(artwork_match)),
+                    true,
+                )
+                .field(
+                    "hash distance",
+                    format!(
+                        "{} bits{}",
+                        artwork_match.hash_distance,
+                        match_note(artwork_match)
+                    ),
+                    true,
+                )
+                .field("source", source, true)
+                .field(
+                    "closest artists",
+                    artwork_match
+                        .artists
+                        .iter()
+                        .map(|artist| {
+                            format!(
+                                "{} ({} artworks, {} bits)",
+                                artist.artist_name, artist.artworks, artist.hash_distance
+                            )
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    false,
+                )
+                .field("action taken", &action, true)
+                .footer(CreateEmbedFooter::new(format!(
+                    "user ID: {}",
+                    message.author.id
+                )))
+                .timestamp(message.timestamp)
+        })
+        .collect();
+
+    log_channel
+        .send_message(
+            &ctx.http,
+            CreateMessage::new()
+                .embeds(embeds)
+                .add_files(evidence.into_iter().flatten())
+                .components(detection.components.clone()),
+        )
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when sending log message"))?;
+
+    Ok(())
+}
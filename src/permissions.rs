@@ -0,0 +1,214 @@
+use poise::serenity_prelude::{Member, Permissions, Role, UserId};
+use sqlx::{Pool, Postgres};
+
+use crate::{Context, Error};
+
+/// Whether a user is a global curator, who can manage known AI artists and allowlist images
+/// everywhere.
+pub async fn is_curator(db: &Pool<Postgres>, user_id: UserId) -> anyhow::Result<bool> {
+    let curator = sqlx::query_scalar!(
+        r#"
+            SELECT EXISTS(SELECT 1 FROM curators WHERE user_id = $1) AS "exists!";
+        "#,
+        user_id.get() as i64,
+    )
+    .fetch_one(db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, user = %user_id, "an error occurred when fetching curator"),
+    )?;
+
+    Ok(curator)
+}
+
+/// Whether the invoking user is one of the bot's owners, who can do anything.
+pub fn is_owner(ctx: Context<'_>) -> bool {
+    ctx.framework().options().owners.contains(&ctx.author().id)
+}
+
+/// Whether the invoking user manages the guild the command is invoked in, either by being able to
+/// manage the guild or by having one of its manager roles.
+pub async fn is_manager(ctx: Context<'_>) -> anyhow::Result<bool> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Ok(false);
+    };
+
+    let Some(member) = ctx.author_member().await else {
+        return Ok(false);
+    };
+
+    let settings = ctx.data().settings.get(&ctx.data().db, guild_id).await?;
+
+    if member
+        .roles
+        .iter()
+        .any(|role| settings.manager_roles.contains(role))
+    {
+        return Ok(true);
+    }
+
+    Ok(member_permissions(ctx, &member).is_some_and(|permissions| permissions.manage_guild()))
+}
+
+/// Whether the invoking user administers the guild the command is invoked in, by owning it or
+/// being able to manage it. Unlike [`is_manager`], manager roles don't count.
+pub async fn is_admin(ctx: Context<'_>) -> bool {
+    let Some(member) = ctx.author_member().await else {
+        return false;
+    };
+
+    let owner = ctx
+        .guild()
+        .is_some_and(|guild| guild.owner_id == member.user.id);
+
+    owner || member_permissions(ctx, &member).is_some_and(|permissions| permissions.manage_guild())
+}
+
+/// Whether the invoking user moderates the guild the command is invoked in, by being able to manage
+/// its messages.
+pub async fn is_moderator(ctx: Context<'_>) -> bool {
+    let Some(member) = ctx.author_member().await else {
+        return false;
+    };
+
+    member_permissions(ctx, &member).is_some_and(|permissions| permissions.manage_messages())
+}
+
+/// Whether the invoking user could give a role themselves, by being able to manage roles and
+/// having a higher role than it, so manager roles can't be used to hand out roles above them.
+pub async fn can_give_role(ctx: Context<'_>, role: &Role) -> bool {
+    let Some(member) = ctx.author_member().await else {
+        return false;
+    };
+
+    let permitted =
+        member_permissions(ctx, &member).is_some_and(|permissions| permissions.manage_roles());
+
+    let Some(guild) = ctx.guild() else {
+        return false;
+    };
+
+    outranks(
+        permitted,
+        guild.owner_id == member.user.id,
+        guild
+            .member_highest_role(&member)
+            .map(|highest| highest.position),
+        role.position,
+    )
+}
+
+/// Whether the invoking user could time out everyone the bot can, by being able to moderate
+/// members and having a higher role than the bot, so manager roles can't be used to time out
+/// members above them.
+pub async fn can_time_out(ctx: Context<'_>) -> bool {
+    let Some(guild_id) = ctx.guild_id() else {
+        return false;
+    };
+
+    let Some(member) = ctx.author_member().await else {
+        return false;
+    };
+
+    let Ok(bot) = guild_id
+        .member(ctx, ctx.framework().bot_id)
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when fetching bot member"))
+    else {
+        return false;
+    };
+
+    let permitted =
+        member_permissions(ctx, &member).is_some_and(|permissions| permissions.moderate_members());
+
+    let Some(guild) = ctx.guild() else {
+        return false;
+    };
+
+    outranks(
+        permitted,
+        guild.owner_id == member.user.id,
+        guild
+            .member_highest_role(&member)
+            .map(|highest| highest.position),
+        guild
+            .member_highest_role(&bot)
+            .map_or(0, |highest| highest.position),
+    )
+}
+
+/// Whether a member can act on everything at a role position, by owning the guild or by having
+/// the permission to and a higher role.
+fn outranks(permitted: bool, owner: bool, highest: Option<u16>, position: u16) -> bool {
+    owner || (permitted && highest.is_some_and(|highest| highest > position))
+}
+
+/// Returns the permissions of a member in the channel a command is invoked in.
+fn member_permissions(ctx: Context<'_>, member: &Member) -> Option<Permissions> {
+    // slash commands come with the member's permissions, which prefix commands have to compute.
+    if let Some(permissions) = member.permissions {
+        return Some(permissions);
+    }
+
+    let guild = ctx.guild()?;
+
+    let channel_id = ctx.channel_id();
+    let channel = guild.channels.get(&channel_id).or_else(|| {
+        guild
+            .threads
+            .iter()
+            .find(|thread| thread.id == channel_id)
+            .and_then(|thread| thread.parent_id)
+            .and_then(|parent_id| guild.channels.get(&parent_id))
+    })?;
+
+    Some(guild.user_permissions_in(channel, member))
+}
+
+/// Lets bot owners and guild managers through.
+pub async fn manager_check(ctx: Context<'_>) -> Result<bool, Error> {
+    Ok(is_owner(ctx) || is_manager(ctx).await?)
+}
+
+/// Lets bot owners and guild admins through, but not manager roles, so those can't change who
+/// manages the bot.
+pub async fn admin_check(ctx: Context<'_>) -> Result<bool, Error> {
+    Ok(is_owner(ctx) || is_admin(ctx).await)
+}
+
+/// Lets bot owners and global curators through.
+pub async fn curator_check(ctx: Context<'_>) -> Result<bool, Error> {
+    Ok(is_owner(ctx) || is_curator(&ctx.data().db, ctx.author().id).await?)
+}
+
+/// Lets bot owners, guild managers and guild moderators through.
+pub async fn moderator_check(ctx: Context<'_>) -> Result<bool, Error> {
+    Ok(is_owner(ctx) || is_moderator(ctx).await || is_manager(ctx).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::outranks;
+
+    #[test]
+    fn refuses_members_without_the_permission() {
+        assert!(!outranks(false, false, Some(10), 5));
+    }
+
+    #[test]
+    fn refuses_members_not_above_the_position() {
+        assert!(!outranks(true, false, Some(5), 5));
+        assert!(!outranks(true, false, Some(4), 5));
+        assert!(!outranks(true, false, None, 0));
+    }
+
+    #[test]
+    fn allows_members_above_the_position() {
+        assert!(outranks(true, false, Some(6), 5));
+    }
+
+    #[test]
+    fn allows_the_guild_owner() {
+        assert!(outranks(false, true, None, 5));
+    }
+}
@@ -1,51 +1,194 @@
 use futures::future::BoxFuture;
 use poise::serenity_prelude::*;
 use rayon::prelude::*;
+use sqlx::PgConnection;
 
 use crate::Data;
-use crate::hashing::compute_dhash;
+use crate::hashing::{
+    HASHERS, ImageHashes, TILE_GRIDS, TILE_HASH_VERSION, compute_tile_hashes, preprocess,
+};
+use crate::index::{IndexedArtwork, IndexedTile};
+use crate::matching::MatchBackend;
+use crate::media::decode_frames;
 use crate::models::database::Platform;
 
-async fn process_image(
-    data: &Data,
-    artist_id: Option<i64>,
-    url: &str,
-    id: i64,
-    platform: Platform,
-) -> anyhow::Result<()> {
+/// Every hash stored for a single artwork.
+struct ArtworkHashes {
+    /// Whether borders were trimmed off the artwork before hashing.
+    trimmed: bool,
+    /// The hashes of every sampled frame, in order.
+    frames: Vec<ImageHashes>,
+    /// The tile hashes of the first frame.
+    tiles: Vec<(i16, [u8; 8])>,
+}
+
+async fn hash_artwork(data: &Data, url: &str) -> anyhow::Result<ArtworkHashes> {
     let response = data
         .booru
         .download(url)
         .await
         .inspect_err(|e| tracing::error!(err = ?e, "an error occurred when fetching image"))?;
 
-    let image = image::load_from_memory(&response)
+    let frames = decode_frames(&response, data.video_decoding.as_ref())
+        .await
         .inspect_err(
             |e| tracing::error!(err = ?e, url = url, "an error occurred when loading image"),
-        )?
-        .to_rgb8();
+        )?;
+
+    let trimmed = data.border_trim.is_some();
+    let frames: Vec<_> = frames
+        .iter()
+        .map(|frame| preprocess(frame, data.border_trim))
+        .collect();
+    // tiles are only stored for the first frame of animations.
+    let tiles = frames
+        .first()
+        .map(|frame| compute_tile_hashes(frame, &TILE_GRIDS))
+        .unwrap_or_default();
+
+    Ok(ArtworkHashes {
+        trimmed,
+        frames: frames
+            .iter()
+            .map(|frame| ImageHashes::compute(frame, trimmed))
+            .collect(),
+        tiles,
+    })
+}
 
-    let dhash = compute_dhash(&image);
+async fn insert_hashes(
+    conn: &mut PgConnection,
+    artwork_id: i64,
+    hashes: &ArtworkHashes,
+) -> anyhow::Result<()> {
+    let mut frame_indices: Vec<i16> = vec![];
+    let mut algorithms: Vec<String> = vec![];
+    let mut versions: Vec<i16> = vec![];
+    let mut hash_values: Vec<Vec<u8>> = vec![];
+
+    for (frame, frame_hashes) in hashes.frames.iter().enumerate() {
+        for (key, hash) in frame_hashes.iter() {
+            frame_indices.push(frame as i16);
+            algorithms.push(key.algorithm.to_string());
+            versions.push(key.version);
+            hash_values.push(hash.to_vec());
+        }
+    }
 
     sqlx::query!(
         r#"
             INSERT INTO
-                artist_artworks (artist_id, platform, original_id, dhash)
+                artwork_hashes (artwork_id, trimmed, frame, algorithm, version, hash)
+            SELECT $1, $2, * FROM UNNEST($3::SMALLINT[], $4::TEXT[], $5::SMALLINT[], $6::BYTEA[]);
+        "#,
+        artwork_id,
+        hashes.trimmed,
+        &frame_indices,
+        &algorithms,
+        &versions,
+        &hash_values,
+    )
+    .execute(&mut *conn)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when inserting artwork hashes to database"),
+    )?;
+
+    let (tile_indices, tile_hashes): (Vec<i16>, Vec<Vec<u8>>) = hashes
+        .tiles
+        .iter()
+        .map(|(tile_index, hash)| (*tile_index, hash.to_vec()))
+        .unzip();
+
+    sqlx::query!(
+        r#"
+            INSERT INTO
+                artwork_tiles (artwork_id, trimmed, version, tile_index, hash)
+            SELECT $1, $2, $3, * FROM UNNEST($4::SMALLINT[], $5::BYTEA[]);
+        "#,
+        artwork_id,
+        hashes.trimmed,
+        TILE_HASH_VERSION,
+        &tile_indices,
+        &tile_hashes,
+    )
+    .execute(&mut *conn)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when inserting artwork tiles to database"),
+    )?;
+
+    Ok(())
+}
+
+async fn process_image(
+    data: &Data,
+    artist_id: Option<i64>,
+    url: &str,
+    id: i64,
+    platform: Platform,
+) -> anyhow::Result<()> {
+    let hashes = hash_artwork(data, url).await?;
+
+    let mut tx = data.db.begin().await.inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when starting transaction"),
+    )?;
+
+    let artwork_id = sqlx::query_scalar!(
+        r#"
+            INSERT INTO
+                artist_artworks (artist_id, platform, original_id)
             VALUES
-                ($1, $2, $3, $4)
-            ON CONFLICT (platform, original_id) DO NOTHING;
+                ($1, $2, $3)
+            ON CONFLICT (platform, original_id) DO NOTHING
+            RETURNING id;
         "#,
         artist_id,
         platform as Platform,
         id,
-        &dhash,
     )
-    .execute(&data.db)
+    .fetch_optional(&mut *tx)
     .await
     .inspect_err(
         |e| tracing::error!(err = ?e, "an error occurred when inserting artist to database"),
     )?;
 
+    let Some(artwork_id) = artwork_id else {
+        return Ok(());
+    };
+
+    insert_hashes(&mut tx, artwork_id, &hashes).await?;
+
+    tx.commit().await.inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when committing transaction"),
+    )?;
+
+    if let Some(artist_id) = artist_id
+        && data.match_backend == MatchBackend::Memory
+    {
+        let artwork = IndexedArtwork {
+            artwork_id,
+            artist_id,
+        };
+
+        for (key, hash) in hashes.frames.iter().flat_map(ImageHashes::iter) {
+            data.index.insert(key, hash, artwork);
+        }
+
+        if data.tile_matching.is_some() {
+            for (tile_index, hash) in &hashes.tiles {
+                data.index.insert_tile(
+                    hashes.trimmed,
+                    hash,
+                    IndexedTile {
+                        artwork,
+                        tile_index: *tile_index,
+                    },
+                );
+            }
+        }
+    }
+
     Ok(())
 }
 
@@ -426,3 +569,140 @@ pub async fn scrape_task(data: &Data) -> anyhow::Result<()> {
 
     Ok(())
 }
+
+/// Re-downloads and rehashes a single artwork, replacing every hash stored for it.
+async fn rehash_artwork(
+    data: &Data,
+    artwork_id: i64,
+    platform: Platform,
+    original_id: i64,
+) -> anyhow::Result<()> {
+    let Some(url) = data
+        .booru
+        .get_file_url(&platform, original_id)
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, artwork_id = artwork_id, "an error occurred when fetching post"))?
+    else {
+        tracing::warn!(
+            artwork_id = artwork_id,
+            original_id = original_id,
+            "skipping artwork whose post is gone"
+        );
+        anyhow::bail!("post {original_id} of artwork {artwork_id} is gone");
+    };
+
+    let hashes = hash_artwork(data, &url).await?;
+
+    let mut tx = data.db.begin().await.inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when starting transaction"),
+    )?;
+
+    sqlx::query!(
+        r#"
+            DELETE FROM artwork_hashes WHERE artwork_id = $1;
+        "#,
+        artwork_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when deleting outdated artwork hashes"),
+    )?;
+
+    sqlx::query!(
+        r#"
+            DELETE FROM artwork_tiles WHERE artwork_id = $1;
+        "#,
+        artwork_id,
+    )
+    .execute(&mut *tx)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when deleting outdated artwork tiles"),
+    )?;
+
+    insert_hashes(&mut tx, artwork_id, &hashes).await?;
+
+    tx.commit().await.inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when committing transaction"),
+    )?;
+
+    Ok(())
+}
+
+/// Rehashes every artwork missing a hash from the current version of any algorithm, so that hashes
+/// from outdated versions get replaced. The bot keeps matching with current hashes meanwhile.
+pub async fn rehash_task(data: &Data) -> anyhow::Result<()> {
+    let (algorithms, versions): (Vec<String>, Vec<i16>) = HASHERS
+        .iter()
+        .map(|hasher| (hasher.id().to_string(), hasher.version()))
+        .unzip();
+
+    let artworks = sqlx::query!(
+        r#"
+            SELECT aw.id, aw.platform AS "platform: Platform", aw.original_id
+            FROM artist_artworks aw
+            WHERE EXISTS (
+                SELECT 1
+                FROM UNNEST($1::TEXT[], $2::SMALLINT[]) AS latest (algorithm, version)
+                WHERE NOT EXISTS (
+                    SELECT 1
+                    FROM artwork_hashes h
+                    WHERE h.artwork_id = aw.id
+                        AND h.algorithm = latest.algorithm
+                        AND h.version = latest.version
+                )
+            ) OR EXISTS (
+                SELECT 1
+                FROM artwork_tiles t
+                WHERE t.artwork_id = aw.id AND t.version <> $3
+            )
+            ORDER BY aw.id;
+        "#,
+        &algorithms,
+        &versions,
+        TILE_HASH_VERSION,
+    )
+    .fetch_all(&data.db)
+    .await
+    .inspect_err(
+        |e| tracing::error!(err = ?e, "an error occurred when fetching outdated artworks"),
+    )?;
+
+    tracing::info!(artworks = artworks.len(), "rehashing outdated artworks");
+
+    let mut success_count = 0;
+    let mut failure_count = 0;
+
+    for chunk in artworks.chunks(5) {
+        let tasks: Vec<_> = chunk
+            .iter()
+            .map(|artwork| {
+                rehash_artwork(
+                    data,
+                    artwork.id,
+                    artwork.platform.clone(),
+                    artwork.original_id,
+                )
+            })
+            .collect();
+
+        let results = futures::future::join_all(tasks).await;
+
+        for result in results {
+            if result.is_ok() {
+                success_count += 1;
+            } else {
+                failure_count += 1;
+            }
+        }
+    }
+
+    tracing::info!(
+        success_count = success_count,
+        failure_count = failure_count,
+        "finished rehashing"
+    );
+
+    Ok(())
+}
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::sync::{Arc, PoisonError, RwLock};
+
+use poise::serenity_prelude::{ChannelId, GuildId, RoleId};
+use sqlx::{Pool, Postgres};
+
+use crate::actions::{ACTIONS, get_action};
+use crate::matching::MATCH_RADIUS;
+
+/// Largest matching radius a guild can configure, past which unrelated images start matching.
+pub const MAX_MATCH_RADIUS: u32 = 20;
+
+/// How the bot behaves in a single guild.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GuildSettings {
+    /// Whether messages are checked for known AI art at all.
+    pub enabled: bool,
+    pub match_radius: u32,
+    /// Identifier of the [`ResponseAction`](crate::actions::ResponseAction) taken on detections.
+    pub response_action: &'static str,
+    /// Channels whose messages aren't checked.
+    pub excluded_channels: Vec<ChannelId>,
+    /// Roles whose members' messages aren't checked.
+    pub excluded_roles: Vec<RoleId>,
+    /// Channel every detection is logged to.
+    pub log_channel: Option<ChannelId>,
+    /// Roles whose members can manage the bot in the guild, on top of those who can manage it.
+    pub manager_roles: Vec<RoleId>,
+}
+
+impl Default for GuildSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            match_radius: MATCH_RADIUS,
+            response_action: ACTIONS[0].id(),
+            excluded_channels: vec![],
+            excluded_roles: vec![],
+            log_channel: None,
+            manager_roles: vec![],
+        }
+    }
+}
+
+/// Settings of every guild, cached in memory after they're first read from the database.
+#[derive(Debug, Clone, Default)]
+pub struct SettingsCache {
+    guilds: Arc<RwLock<HashMap<GuildId, GuildSettings>>>,
+}
+
+impl SettingsCache {
+    /// Returns the settings of a guild, reading them from the database if they aren't cached yet.
+    /// Guilds that never changed their settings get the defaults.
+    pub async fn get(
+        &self,
+        db: &Pool<Postgres>,
+        guild_id: GuildId,
+    ) -> anyhow::Result<GuildSettings> {
+        if let Some(settings) = self
+            .guilds
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&guild_id)
+        {
+            return Ok(settings.clone());
+        }
+
+        let row = sqlx::query!(
+            r#"
+                SELECT
+                    enabled,
+                    match_radius,
+                    response_action,
+                    excluded_channels,
+                    excluded_roles,
+                    log_channel,
+                    manager_roles
+                FROM guild_settings
+                WHERE guild_id = $1;
+            "#,
+            guild_id.get() as i64,
+        )
+        .fetch_optional(db)
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, guild_id = %guild_id, "an error occurred when fetching guild settings"))?;
+
+        let settings = match row {
+            Some(row) => GuildSettings {
+                enabled: row.enabled,
+                match_radius: row.match_radius as u32,
+                response_action: match get_action(&row.response_action) {
+                    Some(action) => action.id(),
+                    None => {
+                        tracing::warn!(guild_id = %guild_id, action = %row.response_action, "an unknown response action is configured, falling back to the default");
+                        ACTIONS[0].id()
+                    }
+                },
+                excluded_channels: row
+                    .excluded_channels
+                    .into_iter()
+                    .map(|id| ChannelId::new(id as u64))
+                    .collect(),
+                excluded_roles: row
+                    .excluded_roles
+                    .into_iter()
+                    .map(|id| RoleId::new(id as u64))
+                    .collect(),
+                log_channel: row.log_channel.map(|id| ChannelId::new(id as u64)),
+                manager_roles: row
+                    .manager_roles
+                    .into_iter()
+                    .map(|id| RoleId::new(id as u64))
+                    .collect(),
+            },
+            None => GuildSettings::default(),
+        };
+
+        self.guilds
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(guild_id, settings.clone());
+
+        Ok(settings)
+    }
+
+    /// Changes the settings of a guild, storing them in the database before caching them.
+    pub async fn update(
+        &self,
+        db: &Pool<Postgres>,
+        guild_id: GuildId,
+        change: impl FnOnce(&mut GuildSettings),
+    ) -> anyhow::Result<GuildSettings> {
+        let mut settings = self.get(db, guild_id).await?;
+        change(&mut settings);
+
+        let excluded_channels: Vec<i64> = settings
+            .excluded_channels
+            .iter()
+            .map(|id| id.get() as i64)
+            .collect();
+        let excluded_roles: Vec<i64> = settings
+            .excluded_roles
+            .iter()
+            .map(|id| id.get() as i64)
+            .collect();
+        let manager_roles: Vec<i64> = settings
+            .manager_roles
+            .iter()
+            .map(|id| id.get() as i64)
+            .collect();
+
+        sqlx::query!(
+            r#"
+                INSERT INTO
+                    guild_settings (
+                        guild_id,
+                        enabled,
+                        match_radius,
+                        response_action,
+                        excluded_channels,
+                        excluded_roles,
+                        log_channel,
+                        manager_roles
+                    )
+                VALUES
+                    ($1, $2, $3, $4, $5, $6, $7, $8)
+                ON CONFLICT (guild_id) DO UPDATE SET
+                    enabled = excluded.enabled,
+                    match_radius = excluded.match_radius,
+                    response_action = excluded.response_action,
+                    excluded_channels = excluded.excluded_channels,
+                    excluded_roles = excluded.excluded_roles,
+                    log_channel = excluded.log_channel,
+                    manager_roles = excluded.manager_roles,
+                    updated_at = NOW();
+            "#,
+            guild_id.get() as i64,
+            settings.enabled,
+            settings.match_radius as i16,
+            settings.response_action,
+            &excluded_channels,
+            &excluded_roles,
+            settings.log_channel.map(|id| id.get() as i64),
+            &manager_roles,
+        )
+        .execute(db)
+        .await
+        .inspect_err(|e| tracing::error!(err = ?e, guild_id = %guild_id, "an error occurred when updating guild settings"))?;
+
+        self.guilds
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(guild_id, settings.clone());
+
+        Ok(settings)
+    }
+}